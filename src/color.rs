@@ -5,7 +5,28 @@ pub(crate) fn convert_file_size_to_color(size: u64) -> (u8, u8, u8) {
     hsv_to_rgb(hue, saturation, value)
 }
 
+/// Entry counts are coloured as if each entry were this many bytes, so that a hundred entries are as blue as a
+/// megabyte and ten million as red as a hundred gigabytes.
+const BYTES_PER_ENTRY: u64 = 10 * 1024;
+
+pub(crate) fn convert_entry_count_to_color(count: u64) -> (u8, u8, u8) {
+    convert_file_size_to_color(count.saturating_mul(BYTES_PER_ENTRY))
+}
+
 const BLUE_HUE: f64 = 210.0;
+const GREEN_HUE: f64 = 120.0;
+
+/// Growth is coloured as sizes are, so that what has grown the most is red. What has shrunk goes from the same
+/// blue to green instead, and what hasn't changed is grey.
+pub(crate) fn convert_growth_to_color(growth: i64) -> (u8, u8, u8) {
+    let (hue, saturation, value) = size_to_hsv(growth.unsigned_abs());
+    let hue = if growth < 0 { GREEN_HUE + hue / BLUE_HUE * (BLUE_HUE - GREEN_HUE) } else { hue };
+    hsv_to_rgb(hue, saturation, value)
+}
+
+pub(crate) fn convert_entry_growth_to_color(growth: i64) -> (u8, u8, u8) {
+    convert_growth_to_color(growth.saturating_mul(BYTES_PER_ENTRY as i64))
+}
 
 fn size_to_hsv(size: u64) -> (f64, f64, f64) {
     const HUE_MIN: f64 = 1024_f64 * 1024_f64;
@@ -30,7 +51,7 @@ fn calc_saturation(size: f64, saturation_max: f64) -> f64 {
     const FACTOR: f64 = 1000_f64;
     let sat = (size / saturation_max) * FACTOR;
     let sat = sat.log10() / FACTOR.log10();
-    ((sat * 100_f64).round() / 100_f64).min(1.0).max(0.0)
+    ((sat * 100_f64).round() / 100_f64).clamp(0.0, 1.0)
 }
 
 fn calc_hue(size: f64, hue_min: f64, hue_max: f64, base_hue: f64) -> f64 {
@@ -43,7 +64,7 @@ fn calc_hue(size: f64, hue_min: f64, hue_max: f64, base_hue: f64) -> f64 {
         let range = (hue_max - hue_min).abs();
         let size_in_range = (size.max(hue_min).min(hue_max) - hue_min).max(0.0);
         let mid = (size_in_range / range) * FACTOR;
-        (1.0 - (mid.log10() / FACTOR.log10())).max(0.0).min(1.0)
+        (1.0 - (mid.log10() / FACTOR.log10())).clamp(0.0, 1.0)
     };
     (hue_scale * base_hue * 100_f64).round() / 100_f64
 }
@@ -53,7 +74,7 @@ mod test {
     use super::*;
 
     mod size_to_hsv {
-        use crate::tui::color::{calc_hue, calc_saturation, calc_value};
+        use crate::color::{calc_hue, calc_saturation, calc_value};
 
         use super::*;
 
@@ -117,6 +138,20 @@ mod test {
             assert_eq!(calc_saturation(750_000_000_000.0, 1_000_000_000_000.0), 0.96, "three-quarter saturation");
         }
 
+        #[test]
+        fn test_entry_count_color() {
+            assert_eq!(convert_entry_count_to_color(100), convert_file_size_to_color(1024 * 1024));
+            assert_eq!(convert_entry_count_to_color(u64::MAX), convert_file_size_to_color(u64::MAX));
+        }
+
+        #[test]
+        fn test_growth_color() {
+            let gigabytes = 200 * 1024 * 1024 * 1024;
+            assert_eq!(convert_growth_to_color(gigabytes), convert_file_size_to_color(gigabytes as u64));
+            assert_eq!(convert_growth_to_color(-gigabytes), hsv_to_rgb(GREEN_HUE, 1.0, 0.65));
+            assert_eq!(convert_growth_to_color(0), hsv_to_rgb(BLUE_HUE, 0.0, 1.0));
+        }
+
         #[test]
         fn test_calc_value() {
             assert_eq!(calc_value(1.0, 0.0, 1.0, 0.0), 0.0, "max size is min value");
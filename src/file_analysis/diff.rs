@@ -0,0 +1,168 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::file_analysis::file_types::{DirectoryEntry, SizeMeasure};
+
+/// An entry's size in one of the scans by each measure.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub(crate) struct DiffSizes {
+    pub(crate) len: u64,
+    pub(crate) disk_len: u64,
+    pub(crate) entries: u64,
+}
+
+impl DiffSizes {
+    fn of(entry: &DirectoryEntry) -> DiffSizes {
+        let size = |measure| entry.size(measure).map_or(0, |size| size.0);
+        DiffSizes {
+            len: size(SizeMeasure::ApparentSize),
+            disk_len: size(SizeMeasure::DiskUsage),
+            entries: size(SizeMeasure::EntryCount),
+        }
+    }
+    pub(crate) fn size(&self, measure: SizeMeasure) -> u64 {
+        match measure {
+            SizeMeasure::ApparentSize => self.len,
+            SizeMeasure::DiskUsage => self.disk_len,
+            SizeMeasure::EntryCount => self.entries,
+        }
+    }
+}
+
+/// An entry in either of two scans of a tree, with its sizes in each and what is below it in either, those that
+/// have changed the most first.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DiffEntry {
+    /// Where it is in the new tree, or would be if it has been deleted.
+    pub(crate) path: PathBuf,
+    pub(crate) is_dir: bool,
+    /// None if it is new.
+    pub(crate) old: Option<DiffSizes>,
+    /// None if it has been deleted.
+    pub(crate) new: Option<DiffSizes>,
+    pub(crate) entries: Vec<DiffEntry>,
+}
+
+impl DiffEntry {
+    pub(crate) fn old_size(&self, measure: SizeMeasure) -> u64 { self.old.map_or(0, |old| old.size(measure)) }
+    pub(crate) fn new_size(&self, measure: SizeMeasure) -> u64 { self.new.map_or(0, |new| new.size(measure)) }
+    /// How much it has grown by, or shrunk by if negative.
+    pub(crate) fn growth(&self, measure: SizeMeasure) -> i64 {
+        self.new_size(measure) as i64 - self.old_size(measure) as i64
+    }
+    pub(crate) fn is_new(&self) -> bool { self.old.is_none() }
+    pub(crate) fn is_deleted(&self) -> bool { self.new.is_none() }
+    pub(crate) fn name(&self) -> String {
+        let name = self.path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+        if self.is_dir {
+            format!("{}{}", name, std::path::MAIN_SEPARATOR)
+        } else {
+            name
+        }
+    }
+    pub(crate) fn find(&self, path: &Path) -> Option<&DiffEntry> {
+        if self.path == path {
+            return Some(self);
+        }
+        let child = self.entries.iter().find(|entry| path.starts_with(&entry.path))?;
+        child.find(path)
+    }
+    /// Re-sorts every folder by how much its entries have grown or shrunk by `measure`.
+    pub(crate) fn sort_by(&mut self, measure: SizeMeasure) {
+        for entry in self.entries.iter_mut() {
+            entry.sort_by(measure);
+        }
+        self.entries.sort_by_key(|entry| (Reverse(entry.growth(measure).unsigned_abs()), entry.path.clone()));
+    }
+}
+
+/// Combines two scans of a tree, matching entries by their paths from its root so that the roots needn't be the
+/// same, e.g. for a tree that has been moved or restored elsewhere. Rolled up entries are compared separately.
+pub(crate) fn diff_trees(old: &DirectoryEntry, new: &DirectoryEntry) -> DiffEntry {
+    let mut diff = diff_entries(new.path().to_path_buf(), Some(old), Some(new));
+    diff.sort_by(SizeMeasure::ApparentSize);
+    diff
+}
+
+fn diff_entries(path: PathBuf, old: Option<&DirectoryEntry>, new: Option<&DirectoryEntry>) -> DiffEntry {
+    let mut children: BTreeMap<OsString, (Option<&DirectoryEntry>, Option<&DirectoryEntry>)> = BTreeMap::new();
+    let name = |entry: &DirectoryEntry| entry.path().file_name().map(OsString::from).unwrap_or_default();
+    for child in old.map(DirectoryEntry::unrolled_entries).unwrap_or_default() {
+        children.entry(name(child)).or_default().0 = Some(child);
+    }
+    for child in new.map(DirectoryEntry::unrolled_entries).unwrap_or_default() {
+        children.entry(name(child)).or_default().1 = Some(child);
+    }
+    let entries = children
+        .into_iter()
+        .map(|(name, (old_child, new_child))| diff_entries(path.join(name), old_child, new_child))
+        .collect();
+    DiffEntry {
+        is_dir: new.or(old).is_some_and(DirectoryEntry::is_dir),
+        old: old.map(DiffSizes::of),
+        new: new.map(DiffSizes::of),
+        path,
+        entries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::file_analysis::diff::diff_trees;
+    use crate::file_analysis::file_types::SizeMeasure;
+    use crate::file_analysis::mock_utils::{file, folder};
+
+    #[test]
+    fn test_diff_trees() {
+        let old = folder(
+            "old",
+            vec![
+                file(100, "old/a"),
+                file(50, "old/deleted"),
+                folder("old/logs", vec![file(10, "old/logs/1"), file(10, "old/logs/2")], false),
+            ],
+            true,
+        );
+        let new = folder(
+            "new",
+            vec![
+                file(90, "new/a"),
+                file(5, "new/new"),
+                folder("new/logs", vec![file(10, "new/logs/1"), file(200, "new/logs/2")], false),
+            ],
+            true,
+        );
+
+        let diff = diff_trees(&old, &new);
+
+        assert_eq!(
+            (170, 305, 135),
+            (
+                diff.old_size(SizeMeasure::ApparentSize),
+                diff.new_size(SizeMeasure::ApparentSize),
+                diff.growth(SizeMeasure::ApparentSize)
+            )
+        );
+        let order: Vec<(&Path, i64)> =
+            diff.entries.iter().map(|entry| (entry.path.as_path(), entry.growth(SizeMeasure::ApparentSize))).collect();
+        assert_eq!(
+            vec![
+                (Path::new("new/logs"), 190),
+                (Path::new("new/deleted"), -50),
+                (Path::new("new/a"), -10),
+                (Path::new("new/new"), 5)
+            ],
+            order
+        );
+        assert!(diff.entries[0].is_dir);
+        assert!(diff.entries[1].is_deleted());
+        assert!(diff.entries[3].is_new());
+        let logs = diff.find(Path::new("new/logs/2")).expect("a file");
+        assert_eq!(190, logs.growth(SizeMeasure::ApparentSize));
+        assert_eq!(Some(2), diff.entries[0].old.map(|old| old.entries));
+    }
+}
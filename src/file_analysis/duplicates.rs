@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::file_analysis::file_system_proxy_traits::FileSystemProxy;
+use crate::file_analysis::file_types::Byteable;
+
+/// How much of the start of files is hashed first, to tell most files of the same length apart without reading
+/// all of them.
+const PARTIAL_LEN: u64 = 4096;
+
+/// Files with the same contents.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DuplicateSet {
+    pub(crate) len: u64,
+    pub(crate) paths: Vec<PathBuf>,
+}
+
+impl DuplicateSet {
+    /// The space that keeping only one of the copies would free.
+    pub(crate) fn wasted(&self) -> u64 { self.len * (self.paths.len() as u64 - 1) }
+}
+
+/// Finds which of `files`, given with their lengths, have the same contents. Those of the same length are compared
+/// by a hash of their start, then those that still match by a hash of all of their contents, in parallel on a pool of
+/// `threads` threads as the scan is. Empty files and those that can't be read are left out. The sets are sorted by
+/// how much space they waste, most first.
+pub(crate) fn find_duplicates(
+    files: Vec<(PathBuf, u64)>, file_operations: &impl FileSystemProxy, threads: usize,
+) -> Vec<DuplicateSet> {
+    match ThreadPoolBuilder::new().num_threads(threads).build() {
+        Ok(pool) => pool.install(|| compare_files(files, file_operations)),
+        Err(_) => compare_files(files, file_operations),
+    }
+}
+
+fn compare_files(files: Vec<(PathBuf, u64)>, file_operations: &impl FileSystemProxy) -> Vec<DuplicateSet> {
+    let mut by_len: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, len) in files.into_iter().filter(|&(_, len)| len > 0) {
+        by_len.entry(len).or_default().push(path);
+    }
+    let same_len: Vec<DuplicateSet> =
+        by_len.into_iter().map(|(len, paths)| DuplicateSet { len, paths }).filter(|set| set.paths.len() > 1).collect();
+
+    let same_start = regroup(same_len, |path| hash(file_operations, path, Some(PARTIAL_LEN)));
+    // the start of small files is all of them
+    let (mut sets, partly_hashed): (Vec<DuplicateSet>, Vec<DuplicateSet>) =
+        same_start.into_iter().partition(|set| set.len <= PARTIAL_LEN);
+    sets.extend(regroup(partly_hashed, |path| hash(file_operations, path, None)));
+
+    for set in sets.iter_mut() {
+        set.paths.sort();
+    }
+    sets.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    sets
+}
+
+/// Splits each set into those with the same hash, dropping any left with only one file.
+fn regroup(sets: Vec<DuplicateSet>, hash: impl Fn(&Path) -> Option<blake3::Hash> + Sync) -> Vec<DuplicateSet> {
+    sets.into_par_iter()
+        .flat_map_iter(|set| {
+            let hashed: Vec<(blake3::Hash, PathBuf)> =
+                set.paths.into_par_iter().filter_map(|path| hash(&path).map(|hash| (hash, path))).collect();
+            let mut by_hash: HashMap<blake3::Hash, Vec<PathBuf>> = HashMap::new();
+            for (hash, path) in hashed {
+                by_hash.entry(hash).or_default().push(path);
+            }
+            by_hash.into_values().filter(|paths| paths.len() > 1).map(move |paths| DuplicateSet { len: set.len, paths })
+        })
+        .collect()
+}
+
+/// A hash of the first `limit` bytes of the file, or all of it.
+fn hash(file_operations: &impl FileSystemProxy, path: &Path, limit: Option<u64>) -> Option<blake3::Hash> {
+    let mut file = file_operations.open(path).ok()?;
+    let mut hasher = blake3::Hasher::new();
+    let copied = match limit {
+        Some(limit) => io::copy(&mut file.by_ref().take(limit), &mut hasher),
+        None => io::copy(&mut file, &mut hasher),
+    };
+    copied.ok().map(|_| hasher.finalize())
+}
+
+/// A plain text listing of `sets`, each with its copies, and the total that could be freed.
+pub(crate) fn format_report(sets: &[DuplicateSet]) -> String {
+    let mut report = String::new();
+    for set in sets {
+        report +=
+            &format!("{} copies of {}, wasting {}:\n", set.paths.len(), Byteable(set.len), Byteable(set.wasted()));
+        for path in set.paths.iter() {
+            report += &format!("  {}\n", path.display());
+        }
+    }
+    let wasted: u64 = sets.iter().map(DuplicateSet::wasted).sum();
+    report += &format!("duplicate sets: {}, wasting: {}\n", sets.len(), Byteable(wasted));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use crate::file_analysis::duplicates::{find_duplicates, format_report, DuplicateSet};
+    use crate::file_analysis::file_system_proxy_traits::MockFileSystemProxy;
+    use crate::file_analysis::mock_utils;
+    use crate::file_analysis::mock_utils::MockEntry;
+
+    #[test]
+    fn test_find_duplicates() {
+        // longer than is hashed at first, and differing only after that
+        let long = "x".repeat(5000);
+        let contents = [
+            ("a", "same".to_string()),
+            ("b", "diff".to_string()),
+            ("d", "same".to_string()),
+            ("e", String::new()),
+            ("f", String::new()),
+            ("g", format!("{}a", long)),
+            ("h", format!("{}b", long)),
+            ("i", format!("{}a", long)),
+        ];
+        let file = |name: &'static str| {
+            let (_, content) = contents.iter().find(|(file, _)| *file == name).expect("a file");
+            MockEntry::Contents(name, content.clone())
+        };
+        let tree = vec![
+            file("a"),
+            file("b"),
+            MockEntry::Folder("c", vec![file("d"), file("e")]),
+            file("f"),
+            file("g"),
+            file("h"),
+            file("i"),
+        ];
+        let (_, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let files = contents
+            .iter()
+            .map(|(name, content)| {
+                let path =
+                    if ["d", "e"].contains(name) { format!("current/c/{}", name) } else { format!("current/{}", name) };
+                (PathBuf::from(path), content.len() as u64)
+            })
+            .collect();
+
+        let sets = find_duplicates(files, &mock_file_operations, 2);
+
+        let expected = vec![
+            DuplicateSet { len: 5001, paths: vec![PathBuf::from("current/g"), PathBuf::from("current/i")] },
+            DuplicateSet { len: 4, paths: vec![PathBuf::from("current/a"), PathBuf::from("current/c/d")] },
+        ];
+        assert_eq!(expected, sets);
+        assert_eq!(5001, sets[0].wasted());
+    }
+
+    #[test]
+    fn test_find_duplicates_on_threads() {
+        let mut mock_file_operations = MockFileSystemProxy::new();
+        mock_file_operations.expect_open().returning(|_| {
+            assert_eq!(3, rayon::current_num_threads());
+            Ok(Box::new(Cursor::new("same")))
+        });
+        let files = vec![(PathBuf::from("current/a"), 4), (PathBuf::from("current/b"), 4)];
+
+        let sets = find_duplicates(files, &mock_file_operations, 3);
+
+        assert_eq!(
+            vec![DuplicateSet { len: 4, paths: vec![PathBuf::from("current/a"), PathBuf::from("current/b")] }],
+            sets
+        );
+    }
+
+    #[test]
+    fn test_format_report() {
+        let sets =
+            vec![DuplicateSet { len: 2048, paths: vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")] }];
+        let report = format_report(&sets);
+        assert_eq!("3 copies of 2 KB, wasting 4 KB:\n  a\n  b\n  c\nduplicate sets: 1, wasting: 4 KB\n", report);
+    }
+}
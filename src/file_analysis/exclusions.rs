@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+use globset::GlobBuilder;
+// bytes, as globs compile to regexes that can match invalid UTF-8
+use regex::bytes::{Regex, RegexSet};
+
+/// Excluded unless scanning one file system, where they are other file systems anyway.
+pub(crate) const DEFAULT_EXCLUDES: [&str; 3] = [r"re:^/proc$", r"re:^/sys$", r"re:^/mnt/\w*$"];
+
+const REGEX_PREFIX: &str = "re:";
+const GLOB_PREFIX: &str = "glob:";
+
+/// Rules for which entries aren't scanned. A rule is a regex if it starts with "re:", otherwise a glob, optionally
+/// starting with "glob:". Globs containing a separator match the whole path, otherwise just the name. Regexes are
+/// matched against the whole path. Entries matching an include rule are never excluded.
+#[derive(Default)]
+pub(crate) struct Exclusions {
+    excludes: Vec<String>,
+    exclude_set: RegexSet,
+    include_set: RegexSet,
+}
+
+impl Exclusions {
+    pub(crate) fn new(excludes: Vec<String>, includes: &[String]) -> Result<Exclusions, PatternError> {
+        let exclude_set = compile(&excludes)?;
+        let include_set = compile(includes)?;
+        Ok(Exclusions { excludes, exclude_set, include_set })
+    }
+
+    /// The first rule excluding `path`, if it isn't included.
+    pub(crate) fn excluded_by(&self, path: &Path) -> Option<&str> {
+        let path = path.display().to_string();
+        if self.include_set.is_match(path.as_bytes()) {
+            return None;
+        }
+        self.exclude_set.matches(path.as_bytes()).iter().next().map(|index| self.excludes[index].as_str())
+    }
+}
+
+fn compile(rules: &[String]) -> Result<RegexSet, PatternError> {
+    let regexes = rules.iter().map(|rule| to_regex(rule)).collect::<Result<Vec<String>, PatternError>>()?;
+    RegexSet::new(regexes).map_err(|error| PatternError { rule: rules.join(", "), message: error.to_string() })
+}
+
+fn to_regex(rule: &str) -> Result<String, PatternError> {
+    if let Some(regex) = rule.strip_prefix(REGEX_PREFIX) {
+        // checked on its own, so that an error names the rule
+        return Regex::new(regex)
+            .map(|_| regex.to_string())
+            .map_err(|error| PatternError { rule: rule.to_string(), message: error.to_string() });
+    }
+    let glob = rule.strip_prefix(GLOB_PREFIX).unwrap_or(rule);
+    let glob = if glob.contains('/') { glob.to_string() } else { format!("**/{}", glob) };
+    GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.regex().to_string())
+        .map_err(|error| PatternError { rule: rule.to_string(), message: error.kind().to_string() })
+}
+
+/// Reads the `exclude` and `include` rules from a config file, where each line is either of those followed by a
+/// rule, blank, or a comment starting with '#'. Returns the excludes and the includes.
+pub(crate) fn read_config(path: &Path) -> Result<(Vec<String>, Vec<String>), PatternError> {
+    let config = fs::read_to_string(path)
+        .map_err(|error| PatternError { rule: path.display().to_string(), message: error.to_string() })?;
+    let (mut excludes, mut includes) = (vec![], vec![]);
+    for line in config.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        match line.split_once(char::is_whitespace) {
+            Some(("exclude", rule)) => excludes.push(rule.trim().to_string()),
+            Some(("include", rule)) => includes.push(rule.trim().to_string()),
+            _ => {
+                return Err(PatternError {
+                    rule: line.to_string(),
+                    message: format!("expected \"exclude <rule>\" or \"include <rule>\" in {}", path.display()),
+                })
+            }
+        }
+    }
+    Ok((excludes, includes))
+}
+
+/// Where the config file is read from when none is given.
+pub(crate) fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("fodlereyez").join("config"))
+}
+
+#[derive(Debug)]
+pub(crate) struct PatternError {
+    rule: String,
+    message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "invalid rule `{}`: {}", self.rule, self.message) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::file_analysis::exclusions::{Exclusions, DEFAULT_EXCLUDES};
+
+    fn exclusions(excludes: &[&str], includes: &[&str]) -> Exclusions {
+        let to_strings = |rules: &[&str]| rules.iter().map(|rule| rule.to_string()).collect::<Vec<String>>();
+        Exclusions::new(to_strings(excludes), &to_strings(includes)).expect("valid rules")
+    }
+
+    #[test]
+    fn test_regex() {
+        let exclusions = exclusions(&DEFAULT_EXCLUDES, &[]);
+        assert_eq!(Some(r"re:^/mnt/\w*$"), exclusions.excluded_by(Path::new("/mnt/c")));
+        assert_eq!(Some("re:^/proc$"), exclusions.excluded_by(Path::new("/proc")));
+        assert_eq!(None, exclusions.excluded_by(Path::new("/home/proc")));
+    }
+
+    #[test]
+    fn test_glob_matches_name() {
+        let exclusions = exclusions(&["*.iso", "glob:node_modules"], &[]);
+        assert_eq!(Some("*.iso"), exclusions.excluded_by(Path::new("/home/me/big.iso")));
+        assert_eq!(Some("glob:node_modules"), exclusions.excluded_by(Path::new("/src/app/node_modules")));
+        assert_eq!(None, exclusions.excluded_by(Path::new("/src/node_modules/package.json")));
+    }
+
+    #[test]
+    fn test_glob_with_separator_matches_path() {
+        let exclusions = exclusions(&["/srv/*/cache"], &[]);
+        assert_eq!(Some("/srv/*/cache"), exclusions.excluded_by(Path::new("/srv/web/cache")));
+        assert_eq!(None, exclusions.excluded_by(Path::new("/srv/web/app/cache")));
+    }
+
+    #[test]
+    fn test_include_overrides_exclude() {
+        let exclusions = exclusions(&DEFAULT_EXCLUDES, &["/mnt/data"]);
+        assert_eq!(None, exclusions.excluded_by(Path::new("/mnt/data")));
+        assert!(exclusions.excluded_by(Path::new("/mnt/backup")).is_some());
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        assert!(Exclusions::new(vec!["re:(".to_string()], &[]).is_err());
+        assert!(Exclusions::new(vec!["a[".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_analysis::file_types::FileTotal;
+
+/// The totals of the files below a folder by their lower case extension, where those without one are under "".
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct ExtensionTotals(pub(crate) BTreeMap<String, FileTotal>);
+
+impl ExtensionTotals {
+    pub(crate) fn add_file(&mut self, path: &Path, total: &FileTotal) {
+        self.0.entry(extension_of(path)).or_default().add(total);
+    }
+    pub(crate) fn add(&mut self, other: &ExtensionTotals) {
+        for (extension, total) in other.0.iter() {
+            self.0.entry(extension.clone()).or_default().add(total);
+        }
+    }
+}
+
+/// A file's lower case extension, without the ".", or "" if it hasn't got one.
+pub(crate) fn extension_of(path: &Path) -> String {
+    path.extension().map_or(String::new(), |extension| extension.to_string_lossy().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::file_analysis::extensions::{extension_of, ExtensionTotals};
+    use crate::file_analysis::file_types::FileTotal;
+
+    #[test]
+    fn test_extension_of() {
+        assert_eq!("mp4", extension_of(Path::new("videos/Holiday.MP4")));
+        assert_eq!("gz", extension_of(Path::new("logs/old.tar.gz")));
+        assert_eq!("", extension_of(Path::new("home/.bashrc")));
+        assert_eq!("", extension_of(Path::new("bin/ls")));
+    }
+
+    #[test]
+    fn test_add_totals() {
+        let mut totals = ExtensionTotals::default();
+        totals.add_file(Path::new("a.log"), &FileTotal { len: 10, disk_len: 4096, files: 1 });
+        let mut other = ExtensionTotals::default();
+        other.add_file(Path::new("b.LOG"), &FileTotal { len: 5, disk_len: 4096, files: 1 });
+        other.add_file(Path::new("c"), &FileTotal { len: 1, disk_len: 4096, files: 1 });
+        totals.add(&other);
+
+        assert_eq!(Some(&FileTotal { len: 15, disk_len: 8192, files: 2 }), totals.0.get("log"));
+        assert_eq!(Some(&FileTotal { len: 1, disk_len: 4096, files: 1 }), totals.0.get(""));
+        assert_eq!(2, totals.0.len());
+    }
+}
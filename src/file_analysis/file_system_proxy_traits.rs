@@ -1,26 +1,46 @@
 use std::error::Error;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_analysis::owners::Owner;
 
 #[cfg(test)]
 use mockall::automock;
 
-#[cfg_attr(test, automock)]
-pub(crate) trait FileSystemProxy {
-    fn read_dir(
-        &self, directory: &PathBuf,
-    ) -> Result<Box<dyn ReadDirProxy<Item = Result<Box<dyn DirPathEntryProxy>, Box<dyn Error>>>>, Box<dyn Error>>;
-    fn metadata(&self, path: &PathBuf) -> Result<Box<dyn MetadataProxy>, Box<dyn Error>>;
+pub(crate) type DirEntryResult = Result<Box<dyn DirPathEntryProxy>, Box<dyn Error>>;
+pub(crate) type ReadDirResult = Result<Box<dyn ReadDirProxy<Item = DirEntryResult>>, Box<dyn Error>>;
+
+/// The device and inode number of a file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub(crate) struct FileId {
+    pub(crate) device: u64,
+    pub(crate) inode: u64,
 }
 
-pub(crate) trait ReadDirProxy: Iterator {
-    fn path(&mut self) -> PathBuf;
+/// Must be `Sync` as directories are read from a pool of worker threads.
+#[cfg_attr(test, automock)]
+pub(crate) trait FileSystemProxy: Sync {
+    fn read_dir(&self, directory: &Path) -> ReadDirResult;
+    fn metadata(&self, path: &Path) -> Result<Box<dyn MetadataProxy>, Box<dyn Error>>;
+    /// The type of the file system mounted at `path`, if it is a mount point and that is known.
+    fn file_system_type(&self, path: &Path) -> Option<String>;
+    /// What the symlink at `path` links to, as it is written.
+    fn read_link(&self, path: &Path) -> Option<PathBuf>;
+    /// The absolute path of `path` with all symlinks resolved.
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf>;
+    /// Opens the file at `path` to read its contents.
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn Read + Send>>;
 }
 
+pub(crate) trait ReadDirProxy: Iterator {}
+
 #[cfg_attr(test, automock)]
 pub(crate) trait DirPathEntryProxy {
     fn path(&self) -> PathBuf;
     fn file_type(&self) -> std::io::Result<Box<dyn FileTypeProxy>>;
-    fn metadata(&self) -> Result<Box<dyn MetadataProxy>, Box<dyn Error>>;
 }
 
 #[cfg_attr(test, automock)]
@@ -31,7 +51,22 @@ pub(crate) trait FileTypeProxy {
 
 #[cfg_attr(test, automock)]
 pub(crate) trait MetadataProxy {
+    fn is_dir(&self) -> bool;
     fn len(&self) -> u64;
+    /// The space allocated on disk, which can be smaller than `len` for sparse files or larger for small files.
+    fn disk_len(&self) -> u64;
+    /// The number of hard links to the file.
+    fn links(&self) -> u64;
+    /// Identifies the file, and so which paths are hard links to the same one.
+    fn file_id(&self) -> FileId;
+    /// When the contents were last changed, if the platform records it.
+    fn modified(&self) -> Option<SystemTime>;
+    /// When the contents were last read, if the platform records it.
+    fn accessed(&self) -> Option<SystemTime>;
+    /// When the metadata was last changed, which on Windows, where that isn't recorded, is when it was created.
+    fn changed(&self) -> Option<SystemTime>;
+    /// Who owns the file, where the platform has user and group ids.
+    fn owner(&self) -> Option<Owner>;
 
     #[cfg(target_os = "windows")]
     fn file_attributes(&self) -> u32;
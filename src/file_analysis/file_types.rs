@@ -1,38 +1,356 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::error::Error;
 use std::path::{Path, PathBuf};
-use std::{fmt, mem};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_analysis::extensions::{extension_of, ExtensionTotals};
+use crate::file_analysis::file_system_proxy_traits::FileId;
+use crate::file_analysis::owners::{Owner, OwnerTotals};
+use crate::file_analysis::snapshot::{optional_path_bytes, path_bytes};
+use std::{fmt, io, mem};
 
 pub(crate) const ROLLUP_NAME: &str = "<other files...>";
 
-#[derive(PartialEq, Debug)]
+/// Which size entries are measured, sorted and coloured by: the amount of data in them, or the space allocated to
+/// them on disk, which differs for sparse files, compressed file systems and small files in large blocks.
+/// Or how many entries there are in them, for finding where the inodes have gone.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub(crate) enum SizeMeasure {
+    #[default]
+    ApparentSize,
+    DiskUsage,
+    EntryCount,
+}
+
+impl SizeMeasure {
+    /// The next measure, going round all of them.
+    pub(crate) fn cycled(self) -> SizeMeasure {
+        match self {
+            SizeMeasure::ApparentSize => SizeMeasure::DiskUsage,
+            SizeMeasure::DiskUsage => SizeMeasure::EntryCount,
+            SizeMeasure::EntryCount => SizeMeasure::ApparentSize,
+        }
+    }
+    /// Entry counts are plain numbers, other sizes are in bytes.
+    pub(crate) fn format(self, size: Byteable) -> String {
+        match self {
+            SizeMeasure::EntryCount => size.0.to_string(),
+            _ => size.to_string(),
+        }
+    }
+    /// The measure in bytes closest to this one, for comparing with sizes given in bytes.
+    pub(crate) fn bytes(self) -> SizeMeasure {
+        match self {
+            SizeMeasure::EntryCount => SizeMeasure::ApparentSize,
+            measure => measure,
+        }
+    }
+}
+
+impl fmt::Display for SizeMeasure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SizeMeasure::ApparentSize => write!(f, "size"),
+            SizeMeasure::DiskUsage => write!(f, "disk usage"),
+            SizeMeasure::EntryCount => write!(f, "entries"),
+        }
+    }
+}
+
+/// For a file with more than one hard link, how many it has, and whether this is the link its size is counted
+/// against, i.e. the first one scanned. The links to the same file have the same `id`.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HardLinks {
+    pub(crate) count: u64,
+    pub(crate) is_counted: bool,
+    pub(crate) id: FileId,
+}
+
+/// The files with more than one hard link that are counted in a tree, split by whether they are counted below a
+/// path or elsewhere, and where the links to those below it are elsewhere, one of which has to be counted instead
+/// if the path is removed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct PathLinks {
+    pub(crate) counted_elsewhere: HashSet<FileId>,
+    pub(crate) counted_below: HashSet<FileId>,
+    pub(crate) other_links: Vec<PathBuf>,
+}
+
+/// How many files and folders there are below a folder, and how many entries of any kind, including those.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct EntryCounts {
+    pub(crate) files: u64,
+    pub(crate) dirs: u64,
+    pub(crate) total: u64,
+}
+
+impl EntryCounts {
+    fn add(self, other: EntryCounts) -> EntryCounts {
+        EntryCounts { files: self.files + other.files, dirs: self.dirs + other.dirs, total: self.total + other.total }
+    }
+}
+
+/// How much of a folder's files have something in common, such as their owner or extension.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct FileTotal {
+    pub(crate) len: u64,
+    pub(crate) disk_len: u64,
+    pub(crate) files: u64,
+}
+
+impl FileTotal {
+    /// A single file.
+    fn of(file: &DirectoryEntry) -> FileTotal {
+        FileTotal {
+            len: file.size(SizeMeasure::ApparentSize).map_or(0, |size| size.0),
+            disk_len: file.size(SizeMeasure::DiskUsage).map_or(0, |size| size.0),
+            files: 1,
+        }
+    }
+    pub(crate) fn size(&self, measure: SizeMeasure) -> Byteable {
+        match measure {
+            SizeMeasure::ApparentSize => Byteable(self.len),
+            SizeMeasure::DiskUsage => Byteable(self.disk_len),
+            SizeMeasure::EntryCount => Byteable(self.files),
+        }
+    }
+    pub(crate) fn add(&mut self, other: &FileTotal) {
+        self.len += other.len;
+        self.disk_len += other.disk_len;
+        self.files += other.files;
+    }
+}
+
+/// Which of a file's times to show.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum TimeKind {
+    Modified,
+    Accessed,
+    Changed,
+}
+
+impl TimeKind {
+    /// The next time to show, or none after the last.
+    pub(crate) fn next(kind: Option<TimeKind>) -> Option<TimeKind> {
+        match kind {
+            None => Some(TimeKind::Modified),
+            Some(TimeKind::Modified) => Some(TimeKind::Accessed),
+            Some(TimeKind::Accessed) => Some(TimeKind::Changed),
+            Some(TimeKind::Changed) => None,
+        }
+    }
+}
+
+impl fmt::Display for TimeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeKind::Modified => write!(f, "last modified"),
+            TimeKind::Accessed => write!(f, "last accessed"),
+            TimeKind::Changed => write!(f, "last changed"),
+        }
+    }
+}
+
+/// When a file was last modified, accessed and had its metadata changed, or for a folder or rollup the newest of
+/// each of those below it.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct Timestamps {
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) accessed: Option<SystemTime>,
+    pub(crate) changed: Option<SystemTime>,
+}
+
+impl Timestamps {
+    pub(crate) fn get(&self, kind: TimeKind) -> Option<SystemTime> {
+        match kind {
+            TimeKind::Modified => self.modified,
+            TimeKind::Accessed => self.accessed,
+            TimeKind::Changed => self.changed,
+        }
+    }
+    fn newest(self, other: Timestamps) -> Timestamps {
+        Timestamps {
+            modified: self.modified.max(other.modified),
+            accessed: self.accessed.max(other.accessed),
+            changed: self.changed.max(other.changed),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub(crate) enum DirectoryEntry {
-    File { len: Byteable, path: PathBuf, is_hidden: bool },
-    Folder { path: PathBuf, len: Byteable, entries: Vec<DirectoryEntry>, is_root: bool, is_hidden: bool },
-    Link { path: PathBuf, is_root: bool, is_hidden: bool },
-    Rollup { path: PathBuf, len: Byteable, entries: Vec<DirectoryEntry> },
-    Excluded { path: PathBuf, is_dir: bool, is_hidden: bool, is_root: bool }
+    File {
+        len: Byteable,
+        disk_len: Byteable,
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        is_hidden: bool,
+        hard_links: Option<HardLinks>,
+        times: Timestamps,
+        owner: Option<Owner>,
+    },
+    Folder {
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        len: Byteable,
+        disk_len: Byteable,
+        entries: Vec<DirectoryEntry>,
+        is_root: bool,
+        is_hidden: bool,
+        is_scanning: bool,
+        /// How many entries below it are unreadable.
+        errors: u64,
+        counts: EntryCounts,
+        /// The newest times of anything below it.
+        times: Timestamps,
+        /// Whose the files below it are, only kept once it is collapsed, as they aren't.
+        owners: Option<Box<OwnerTotals>>,
+        /// What extensions the files below it have, also only kept once it is collapsed.
+        extensions: Option<Box<ExtensionTotals>>,
+        /// Entries smaller than this are rolled up along with its files.
+        min_size: Byteable,
+        /// Whether its entries have been dropped to save memory, keeping only the totals.
+        is_collapsed: bool,
+    },
+    Link {
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        #[serde(with = "optional_path_bytes")]
+        target: Option<PathBuf>,
+        is_root: bool,
+        is_hidden: bool,
+    },
+    Rollup {
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        len: Byteable,
+        disk_len: Byteable,
+        entries: Vec<DirectoryEntry>,
+        errors: u64,
+        counts: EntryCounts,
+        times: Timestamps,
+    },
+    Excluded {
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        is_dir: bool,
+        is_hidden: bool,
+        is_root: bool,
+        /// The exclude rule it matched.
+        rule: String,
+    },
+    /// A folder on another file system than its parent, which isn't scanned unless entered.
+    MountPoint {
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        file_system: Option<String>,
+        is_hidden: bool,
+    },
+    /// Something that couldn't be read, with the error message.
+    Unreadable {
+        #[serde(with = "path_bytes")]
+        path: PathBuf,
+        message: String,
+        is_denied: bool,
+        is_dir: bool,
+        is_hidden: bool,
+    },
 }
 
 //statics
 impl DirectoryEntry {
-    pub(crate) fn new_excluded(path: PathBuf, is_dir: bool, is_hidden: bool, is_root: bool) -> DirectoryEntry {
-            DirectoryEntry::Excluded { path, is_dir, is_hidden, is_root }
+    pub(crate) fn new_excluded(
+        path: PathBuf, is_dir: bool, is_hidden: bool, is_root: bool, rule: String,
+    ) -> DirectoryEntry {
+        DirectoryEntry::Excluded { path, is_dir, is_hidden, is_root, rule }
     }
     fn new_rollup(entries: Vec<DirectoryEntry>, path: PathBuf) -> DirectoryEntry {
-        let len_sum = entries.iter().fold(0_u64, |a, b| b.len().map(|val| val.0).unwrap_or(0) + a);
-        DirectoryEntry::Rollup { path, len: Byteable(len_sum), entries }
+        let len = sum_sizes(&entries, SizeMeasure::ApparentSize);
+        let disk_len = sum_sizes(&entries, SizeMeasure::DiskUsage);
+        let errors = sum_errors(&entries);
+        let counts = sum_counts(&entries);
+        let times = newest(&entries);
+        DirectoryEntry::Rollup { path, len, disk_len, entries, errors, counts, times }
+    }
+    pub(crate) fn new_file(
+        len: Byteable, disk_len: Byteable, path: PathBuf, is_hidden: bool, times: Timestamps, owner: Option<Owner>,
+    ) -> DirectoryEntry {
+        DirectoryEntry::File { len, disk_len, path, is_hidden, hard_links: None, times, owner }
     }
-    pub(crate) fn new_file(len: Byteable, path: PathBuf, is_hidden: bool) -> DirectoryEntry {
-        DirectoryEntry::File { len, path, is_hidden }
+    pub(crate) fn new_hard_link(
+        len: Byteable, disk_len: Byteable, path: PathBuf, is_hidden: bool, hard_links: HardLinks, times: Timestamps,
+        owner: Option<Owner>,
+    ) -> DirectoryEntry {
+        DirectoryEntry::File { len, disk_len, path, is_hidden, hard_links: Some(hard_links), times, owner }
     }
     pub(crate) fn new_folder(
-        len: Byteable, path: PathBuf, is_hidden: bool, entries: Vec<DirectoryEntry>, is_root: bool,
+        len: Byteable, disk_len: Byteable, path: PathBuf, is_hidden: bool, entries: Vec<DirectoryEntry>, is_root: bool,
+    ) -> DirectoryEntry {
+        DirectoryEntry::new_folder_with_min_size(len, disk_len, path, is_hidden, entries, is_root, Byteable(0))
+    }
+    pub(crate) fn new_folder_with_min_size(
+        len: Byteable, disk_len: Byteable, path: PathBuf, is_hidden: bool, entries: Vec<DirectoryEntry>, is_root: bool,
+        min_size: Byteable,
     ) -> DirectoryEntry {
-        let mut entry = DirectoryEntry::Folder { len, entries, path, is_hidden, is_root };
-        entry.rollup();
+        let is_scanning = entries.iter().any(|entry| entry.is_scanning());
+        let errors = sum_errors(&entries);
+        let counts = sum_counts(&entries);
+        let times = newest(&entries);
+        let mut entry = DirectoryEntry::Folder {
+            len,
+            disk_len,
+            entries,
+            path,
+            is_hidden,
+            is_root,
+            is_scanning,
+            errors,
+            counts,
+            times,
+            owners: None,
+            extensions: None,
+            min_size,
+            is_collapsed: false,
+        };
+        entry.rollup(SizeMeasure::ApparentSize);
         entry
     }
-    pub(crate) fn new_link(path: PathBuf, is_root: bool, is_hidden: bool) -> DirectoryEntry {
-        DirectoryEntry::Link { path, is_root, is_hidden }
+    /// A placeholder for a folder whose contents haven't been scanned yet.
+    pub(crate) fn new_scanning_folder(path: PathBuf, is_hidden: bool) -> DirectoryEntry {
+        DirectoryEntry::Folder {
+            len: Byteable(0),
+            disk_len: Byteable(0),
+            entries: vec![],
+            path,
+            is_hidden,
+            is_root: false,
+            is_scanning: true,
+            errors: 0,
+            counts: EntryCounts::default(),
+            times: Timestamps::default(),
+            owners: None,
+            extensions: None,
+            min_size: Byteable(0),
+            is_collapsed: false,
+        }
+    }
+    pub(crate) fn new_link(path: PathBuf, target: Option<PathBuf>, is_root: bool, is_hidden: bool) -> DirectoryEntry {
+        DirectoryEntry::Link { path, target, is_root, is_hidden }
+    }
+    pub(crate) fn new_mount_point(path: PathBuf, file_system: Option<String>, is_hidden: bool) -> DirectoryEntry {
+        DirectoryEntry::MountPoint { path, file_system, is_hidden }
+    }
+    pub(crate) fn new_unreadable(
+        path: PathBuf, error: &(dyn Error + 'static), is_dir: bool, is_hidden: bool,
+    ) -> DirectoryEntry {
+        let is_denied = std::iter::successors(Some(error), |&error| error.source()).any(|error| {
+            error.downcast_ref::<io::Error>().is_some_and(|error| error.kind() == io::ErrorKind::PermissionDenied)
+        });
+        DirectoryEntry::Unreadable { path, message: error.to_string(), is_denied, is_dir, is_hidden }
     }
 }
 
@@ -43,6 +361,8 @@ impl DirectoryEntry {
             DirectoryEntry::Link { is_hidden, .. } => *is_hidden,
             DirectoryEntry::Folder { is_hidden, .. } => *is_hidden,
             DirectoryEntry::Excluded { is_hidden, .. } => *is_hidden,
+            DirectoryEntry::MountPoint { is_hidden, .. } => *is_hidden,
+            DirectoryEntry::Unreadable { is_hidden, .. } => *is_hidden,
             DirectoryEntry::Rollup { .. } => false,
         }
     }
@@ -52,6 +372,8 @@ impl DirectoryEntry {
             DirectoryEntry::Folder { is_root, .. } => *is_root,
             DirectoryEntry::Link { is_root, .. } => *is_root,
             DirectoryEntry::Excluded { is_root, .. } => *is_root,
+            DirectoryEntry::MountPoint { .. } => false,
+            DirectoryEntry::Unreadable { .. } => false,
             DirectoryEntry::Rollup { .. } => false,
         }
     }
@@ -62,6 +384,8 @@ impl DirectoryEntry {
             DirectoryEntry::Link { path, .. } => path.as_path(),
             DirectoryEntry::Rollup { path, .. } => path.as_path(),
             DirectoryEntry::Excluded { path, .. } => path.as_path(),
+            DirectoryEntry::MountPoint { path, .. } => path.as_path(),
+            DirectoryEntry::Unreadable { path, .. } => path.as_path(),
         }
     }
     pub(crate) fn entries(&self) -> Option<&Vec<DirectoryEntry>> {
@@ -69,24 +393,300 @@ impl DirectoryEntry {
             DirectoryEntry::File { .. } => None,
             DirectoryEntry::Link { .. } => None,
             DirectoryEntry::Excluded { .. } => None,
+            DirectoryEntry::MountPoint { .. } => None,
+            DirectoryEntry::Unreadable { .. } => None,
             DirectoryEntry::Folder { entries, .. } => Some(entries),
             DirectoryEntry::Rollup { entries, .. } => Some(entries),
         }
     }
-    fn rollup(&mut self) {
+    /// The entries of a folder, with any that have been rolled up in place of the rollup.
+    pub(crate) fn unrolled_entries(&self) -> Vec<&DirectoryEntry> {
+        let mut unrolled = vec![];
+        for entry in self.entries().into_iter().flatten() {
+            match entry {
+                DirectoryEntry::Rollup { entries, .. } => unrolled.extend(entries),
+                entry => unrolled.push(entry),
+            }
+        }
+        unrolled
+    }
+    pub(crate) fn hard_links(&self) -> Option<HardLinks> {
+        match self {
+            DirectoryEntry::File { hard_links, .. } => *hard_links,
+            _ => None,
+        }
+    }
+    /// How many entries, including this one, are unreadable.
+    pub(crate) fn errors(&self) -> u64 {
+        match self {
+            DirectoryEntry::Folder { errors, .. } => *errors,
+            DirectoryEntry::Rollup { errors, .. } => *errors,
+            DirectoryEntry::Unreadable { .. } => 1,
+            _ => 0,
+        }
+    }
+    /// How many entries there are below a folder or in a rollup.
+    pub(crate) fn counts(&self) -> Option<EntryCounts> {
+        match self {
+            DirectoryEntry::Folder { counts, .. } => Some(*counts),
+            DirectoryEntry::Rollup { counts, .. } => Some(*counts),
+            _ => None,
+        }
+    }
+    /// How much the entry adds to its folder's counts, which for a folder includes itself.
+    fn counted(&self) -> EntryCounts {
+        let counts = self.counts().unwrap_or_default();
+        match self {
+            DirectoryEntry::Rollup { .. } => counts,
+            DirectoryEntry::File { .. } => EntryCounts { files: 1, dirs: 0, total: 1 },
+            _ if self.is_dir() => EntryCounts { dirs: counts.dirs + 1, total: counts.total + 1, ..counts },
+            _ => EntryCounts { total: 1, ..counts },
+        }
+    }
+    /// How much of a folder or rollup belongs to each user and group, added up from the files below it when asked
+    /// for rather than kept for every folder.
+    pub(crate) fn owners(&self) -> Option<OwnerTotals> {
+        matches!(self, DirectoryEntry::Folder { .. } | DirectoryEntry::Rollup { .. }).then(|| {
+            let mut totals = OwnerTotals::default();
+            self.add_owners_to(&mut totals);
+            totals
+        })
+    }
+    fn add_owners_to(&self, totals: &mut OwnerTotals) {
+        match self {
+            DirectoryEntry::File { owner: Some(owner), .. } => {
+                totals.add_file(*owner, &FileTotal::of(self));
+            }
+            DirectoryEntry::Folder { owners: Some(owners), .. } => totals.add(owners),
+            DirectoryEntry::Folder { entries, .. } | DirectoryEntry::Rollup { entries, .. } => {
+                for entry in entries {
+                    entry.add_owners_to(totals);
+                }
+            }
+            _ => {}
+        }
+    }
+    /// How much of a folder or rollup has each extension, added up when asked for as the owners are.
+    pub(crate) fn extensions(&self) -> Option<ExtensionTotals> {
+        matches!(self, DirectoryEntry::Folder { .. } | DirectoryEntry::Rollup { .. }).then(|| {
+            let mut totals = ExtensionTotals::default();
+            self.add_extensions_to(&mut totals);
+            totals
+        })
+    }
+    fn add_extensions_to(&self, totals: &mut ExtensionTotals) {
+        match self {
+            DirectoryEntry::File { path, .. } => totals.add_file(path, &FileTotal::of(self)),
+            DirectoryEntry::Folder { extensions: Some(extensions), .. } => totals.add(extensions),
+            DirectoryEntry::Folder { entries, .. } | DirectoryEntry::Rollup { entries, .. } => {
+                for entry in entries {
+                    entry.add_extensions_to(totals);
+                }
+            }
+            _ => {}
+        }
+    }
+    /// Up to `count` of the largest files with `extension` below the entry, largest first. Files below collapsed
+    /// folders aren't kept, so aren't found.
+    pub(crate) fn largest_files(&self, extension: &str, measure: SizeMeasure, count: usize) -> Vec<&DirectoryEntry> {
+        let mut files: Vec<&DirectoryEntry> = self
+            .files()
+            .into_iter()
+            .filter(|file| matches!(file, DirectoryEntry::File { path, .. } if extension_of(path) == extension))
+            .collect();
+        files.sort_by_key(|file| Reverse(file.size(measure).map_or(0, |size| size.0)));
+        files.truncate(count);
+        files
+    }
+    /// Up to `count` of the largest files anywhere below the entry, including those rolled up, and of the largest
+    /// folders below it without folders of their own, largest first. Collapsed folders count as having none. Hidden
+    /// entries, and everything below them, are left out unless `show_hidden`.
+    pub(crate) fn largest_entries(
+        &self, measure: SizeMeasure, count: usize, show_hidden: bool,
+    ) -> (Vec<&DirectoryEntry>, Vec<&DirectoryEntry>) {
+        fn add_entries<'a>(
+            folder: &'a DirectoryEntry, show_hidden: bool, files: &mut Vec<&'a DirectoryEntry>,
+            folders: &mut Vec<&'a DirectoryEntry>,
+        ) {
+            for entry in folder.unrolled_entries().into_iter().filter(|entry| show_hidden || !entry.is_hidden()) {
+                match entry {
+                    DirectoryEntry::File { .. } => files.push(entry),
+                    DirectoryEntry::Folder { .. } => {
+                        let children = entry.unrolled_entries();
+                        if !children.iter().any(|child| matches!(child, DirectoryEntry::Folder { .. })) {
+                            folders.push(entry);
+                        }
+                        add_entries(entry, show_hidden, files, folders);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let (mut files, mut folders) = (vec![], vec![]);
+        add_entries(self, show_hidden, &mut files, &mut folders);
+        for entries in [&mut files, &mut folders] {
+            entries.sort_by_key(|entry| Reverse(entry.size(measure).map_or(0, |size| size.0)));
+            entries.truncate(count);
+        }
+        (files, folders)
+    }
+    /// The paths and lengths of the files below the entry that could be copies of each other, which leaves out all
+    /// but one of the hard links to each file.
+    pub(crate) fn duplicate_candidates(&self) -> Vec<(PathBuf, u64)> {
+        self.files()
+            .into_iter()
+            .filter_map(|file| match file {
+                DirectoryEntry::File { path, len, hard_links, .. } => {
+                    hard_links.is_none_or(|hard_links| hard_links.is_counted).then(|| (path.clone(), len.0))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// The files with more than one hard link that are counted below the entry, other than below `except`, so that
+    /// scanning `except` again doesn't count them twice.
+    pub(crate) fn counted_links(&self, except: Option<&Path>) -> HashSet<FileId> {
+        self.files()
+            .into_iter()
+            .filter(|file| except.is_none_or(|except| !file.path().starts_with(except)))
+            .filter_map(|file| file.hard_links().filter(|hard_links| hard_links.is_counted))
+            .map(|hard_links| hard_links.id)
+            .collect()
+    }
+    /// How the files with more than one hard link below the entry are counted with respect to `path`.
+    pub(crate) fn links_of(&self, path: &Path) -> PathLinks {
+        let files = self.files();
+        let mut links = PathLinks::default();
+        for file in files.iter() {
+            if let Some(hard_links) = file.hard_links().filter(|hard_links| hard_links.is_counted) {
+                match file.path().starts_with(path) {
+                    true => links.counted_below.insert(hard_links.id),
+                    false => links.counted_elsewhere.insert(hard_links.id),
+                };
+            }
+        }
+        links.other_links = files
+            .into_iter()
+            .filter(|file| !file.path().starts_with(path))
+            .filter(|file| {
+                let hard_links = file.hard_links();
+                hard_links
+                    .is_some_and(|hard_links| !hard_links.is_counted && links.counted_below.contains(&hard_links.id))
+            })
+            .map(|file| file.path().to_path_buf())
+            .collect();
+        links
+    }
+    /// Every file below the entry, or the entry itself if it is one.
+    fn files(&self) -> Vec<&DirectoryEntry> {
+        fn add_files<'a>(entry: &'a DirectoryEntry, files: &mut Vec<&'a DirectoryEntry>) {
+            match entry {
+                DirectoryEntry::File { .. } => files.push(entry),
+                _ => {
+                    for child in entry.entries().into_iter().flatten() {
+                        add_files(child, files);
+                    }
+                }
+            }
+        }
+
+        let mut files = vec![];
+        add_files(self, &mut files);
+        files
+    }
+    pub(crate) fn times(&self) -> Timestamps {
+        match self {
+            DirectoryEntry::File { times, .. } => *times,
+            DirectoryEntry::Folder { times, .. } => *times,
+            DirectoryEntry::Rollup { times, .. } => *times,
+            _ => Timestamps::default(),
+        }
+    }
+    /// How much of the entry hasn't been modified since `since`: all of it if nothing below it has, otherwise
+    /// whatever below it hasn't.
+    pub(crate) fn stale_size(&self, since: SystemTime, measure: SizeMeasure) -> u64 {
+        if self.times().modified.is_some_and(|modified| modified < since) {
+            return self.size(measure).map_or(0, |size| size.0);
+        }
+        self.entries().map_or(0, |entries| entries.iter().map(|entry| entry.stale_size(since, measure)).sum())
+    }
+    pub(crate) fn is_collapsed(&self) -> bool { matches!(self, DirectoryEntry::Folder { is_collapsed: true, .. }) }
+    /// Drops everything below a folder that has been scanned, keeping its totals.
+    pub(crate) fn collapse(self) -> DirectoryEntry {
+        let owners = self.owners().map(Box::new);
+        let extensions = self.extensions().map(Box::new);
+        match self {
+            DirectoryEntry::Folder {
+                len,
+                disk_len,
+                path,
+                is_hidden,
+                is_root,
+                is_scanning: false,
+                errors,
+                counts,
+                times,
+                min_size,
+                ..
+            } => DirectoryEntry::Folder {
+                len,
+                disk_len,
+                entries: vec![],
+                path,
+                is_hidden,
+                is_root,
+                is_scanning: false,
+                errors,
+                counts,
+                times,
+                owners,
+                extensions,
+                min_size,
+                is_collapsed: true,
+            },
+            entry => entry,
+        }
+    }
+    pub(crate) fn is_scanning(&self) -> bool {
+        match self {
+            DirectoryEntry::Folder { is_scanning, .. } => *is_scanning,
+            _ => false,
+        }
+    }
+    /// Re-sorts and re-rolls up every folder in the tree by `measure`.
+    pub(crate) fn sort_by(&mut self, measure: SizeMeasure) {
+        if let DirectoryEntry::Folder { entries, .. } = self {
+            for entry in entries.iter_mut() {
+                entry.sort_by(measure);
+            }
+        }
+        self.rollup(measure);
+    }
+
+    fn rollup(&mut self, measure: SizeMeasure) {
         match self {
             DirectoryEntry::File { .. } => {}
             DirectoryEntry::Excluded { .. } => {}
             DirectoryEntry::Link { .. } => {}
+            DirectoryEntry::MountPoint { .. } => {}
+            DirectoryEntry::Unreadable { .. } => {}
             DirectoryEntry::Rollup { .. } => {}
-            DirectoryEntry::Folder { entries, path, .. } => {
-                let mut old_entries = mem::take(entries);
-                old_entries.sort_unstable_by_key(|a| a.len().map(|val| val.0).unwrap_or(0));
+            DirectoryEntry::Folder { entries, path, min_size, .. } => {
+                // unroll any previous rollup, so that it can be recalculated when entries have changed
+                let mut old_entries = unrolled(mem::take(entries));
+                old_entries.sort_unstable_by_key(|a| a.size(measure).map(|val| val.0).unwrap_or(0));
 
+                // entries still being scanned aren't small, they just haven't been counted yet
+                let is_small = |entry: &DirectoryEntry| {
+                    !entry.is_scanning()
+                        && entry.len().is_some()
+                        && entry.size(measure.bytes()).map_or(0, |val| val.0) < min_size.0
+                };
                 let mut still_rolling_up = true;
                 let mut files = vec![];
                 for entry in old_entries {
-                    if !entry.is_dir() && still_rolling_up {
+                    if (!entry.is_dir() && still_rolling_up) || is_small(&entry) {
                         files.push(entry);
                     } else {
                         still_rolling_up = false;
@@ -97,14 +697,127 @@ impl DirectoryEntry {
                 if !files.is_empty() {
                     entries.push(DirectoryEntry::new_rollup(files, path.clone()));
                 }
-                entries.sort_unstable_by_key(|a| u64::MAX - a.len().map(|val| val.0).unwrap_or(0));
+                entries.sort_unstable_by_key(|a| u64::MAX - a.size(measure).map(|val| val.0).unwrap_or(0));
+            }
+        }
+    }
+
+    /// Replaces the entry with the same path as `replacement` somewhere below this one, e.g. a folder that has
+    /// finished scanning, and recalculates the sizes, scanning state and rollup, by `measure`, of each of its
+    /// ancestors. Returns false if there is no such entry.
+    pub(crate) fn replace(&mut self, replacement: DirectoryEntry, measure: SizeMeasure) -> bool {
+        if self.path() == replacement.path() {
+            *self = replacement;
+            return true;
+        }
+        match self {
+            DirectoryEntry::Folder { entries, .. } => {
+                let target = entries.iter_mut().find(|entry| {
+                    !matches!(entry, DirectoryEntry::Rollup { .. }) && replacement.path().starts_with(entry.path())
+                });
+                let is_replaced = target.map(|entry| entry.replace(replacement, measure)).unwrap_or(false);
+                if is_replaced {
+                    self.recalculate(measure);
+                }
+                is_replaced
+            }
+            _ => false,
+        }
+    }
+
+    /// Puts `entry` in its folder somewhere below this one, in place of any entry with the same path, e.g. a file
+    /// that has been created or modified, and recalculates each of its ancestors by `measure`. Returns false if its
+    /// folder isn't in the tree, or has been collapsed.
+    pub(crate) fn insert(&mut self, entry: DirectoryEntry, measure: SizeMeasure) -> bool {
+        let Some(folder) = entry.path().parent().map(Path::to_path_buf) else {
+            return false;
+        };
+        self.change_folder(&folder, measure, |entries| {
+            entries.retain(|existing| existing.path() != entry.path());
+            entries.push(entry);
+            true
+        })
+    }
+
+    /// Removes the entry at `path` somewhere below this one, e.g. one that has been deleted, and recalculates each of
+    /// its ancestors by `measure`. Returns false if there is no such entry.
+    pub(crate) fn remove(&mut self, path: &Path, measure: SizeMeasure) -> bool {
+        let Some(folder) = path.parent() else {
+            return false;
+        };
+        self.change_folder(folder, measure, |entries| {
+            let count = entries.len();
+            entries.retain(|existing| existing.path() != path);
+            entries.len() != count
+        })
+    }
+
+    /// Changes the entries of the folder at `folder` with `change`, which is given them without a rollup and returns
+    /// whether it changed anything, then recalculates the folder and, if it did, each of its ancestors.
+    fn change_folder<F>(&mut self, folder: &Path, measure: SizeMeasure, change: F) -> bool
+    where
+        F: FnOnce(&mut Vec<DirectoryEntry>) -> bool,
+    {
+        fn find_folder<'a>(entries: &'a mut [DirectoryEntry], folder: &Path) -> Option<&'a mut DirectoryEntry> {
+            for entry in entries.iter_mut() {
+                if let DirectoryEntry::Rollup { entries, .. } = entry {
+                    // small folders are rolled up along with the files
+                    if let Some(found) = find_folder(entries, folder) {
+                        return Some(found);
+                    }
+                } else if matches!(entry, DirectoryEntry::Folder { .. }) && folder.starts_with(entry.path()) {
+                    return Some(entry);
+                }
+            }
+            None
+        }
+
+        let is_folder = self.path() == folder;
+        let DirectoryEntry::Folder { entries, is_collapsed: false, .. } = self else {
+            return false;
+        };
+        if is_folder {
+            let mut unrolled_entries = unrolled(mem::take(entries));
+            let is_changed = change(&mut unrolled_entries);
+            *entries = unrolled_entries;
+            // it has to be rolled up again anyway
+            self.recalculate(measure);
+            return is_changed;
+        }
+        let is_changed = find_folder(entries, folder).is_some_and(|entry| entry.change_folder(folder, measure, change));
+        if is_changed {
+            self.recalculate(measure);
+        }
+        is_changed
+    }
+
+    /// The paths of this folder and those below it whose entries are kept, so not those that have been collapsed.
+    pub(crate) fn folders(&self) -> Vec<&Path> {
+        match self {
+            DirectoryEntry::Folder { path, entries, is_collapsed: false, .. } => {
+                std::iter::once(path.as_path()).chain(entries.iter().flat_map(DirectoryEntry::folders)).collect()
             }
+            DirectoryEntry::Rollup { entries, .. } => entries.iter().flat_map(DirectoryEntry::folders).collect(),
+            _ => vec![],
         }
     }
 
+    fn recalculate(&mut self, measure: SizeMeasure) {
+        if let DirectoryEntry::Folder { entries, len, disk_len, is_scanning, errors, counts, times, .. } = self {
+            *len = sum_sizes(entries, SizeMeasure::ApparentSize);
+            *disk_len = sum_sizes(entries, SizeMeasure::DiskUsage);
+            *is_scanning = entries.iter().any(|entry| entry.is_scanning());
+            *errors = sum_errors(entries);
+            *counts = sum_counts(entries);
+            *times = newest(entries);
+        }
+        self.rollup(measure);
+    }
+
     pub(crate) fn find(&self, match_path: &Path) -> Option<&Self> {
-        fn find_entry<'a>(entries: &'a Vec<DirectoryEntry>, find_path: &Path) -> Option<&'a DirectoryEntry> {
-            entries.iter().find(|&entry| find_path.starts_with(entry.path())).and_then(|entry| {
+        fn find_entry<'a>(entries: &'a [DirectoryEntry], find_path: &Path) -> Option<&'a DirectoryEntry> {
+            // a rollup has the same path as its folder, so more than one entry can be a candidate
+            entries.iter().filter(|&entry| find_path.starts_with(entry.path())).find_map(|entry| {
                 if entry.path() == find_path {
                     Some(entry)
                 } else {
@@ -120,6 +833,8 @@ impl DirectoryEntry {
                 DirectoryEntry::File { .. } => None,
                 DirectoryEntry::Excluded { .. } => None,
                 DirectoryEntry::Link { .. } => None,
+                DirectoryEntry::MountPoint { .. } => None,
+                DirectoryEntry::Unreadable { .. } => None,
                 DirectoryEntry::Folder { entries, .. } => find_entry(entries, match_path),
                 DirectoryEntry::Rollup { entries, .. } => find_entry(entries, match_path),
             }
@@ -133,6 +848,8 @@ impl DirectoryEntry {
             DirectoryEntry::Rollup { path, .. } => path.parent(),
             DirectoryEntry::Link { path, .. } => path.parent(),
             DirectoryEntry::Excluded { path, .. } => path.parent(),
+            DirectoryEntry::MountPoint { path, .. } => path.parent(),
+            DirectoryEntry::Unreadable { path, .. } => path.parent(),
         }
     }
     pub fn has_children(&self) -> bool {
@@ -140,6 +857,9 @@ impl DirectoryEntry {
             DirectoryEntry::File { .. } => false,
             DirectoryEntry::Excluded { .. } => false,
             DirectoryEntry::Link { .. } => false,
+            // it could have, but they haven't been scanned
+            DirectoryEntry::MountPoint { .. } => true,
+            DirectoryEntry::Unreadable { .. } => false,
             DirectoryEntry::Folder { entries, .. } => !entries.is_empty(),
             DirectoryEntry::Rollup { .. } => true,
         }
@@ -151,6 +871,8 @@ impl DirectoryEntry {
             DirectoryEntry::Folder { .. } => true,
             DirectoryEntry::Rollup { .. } => false,
             DirectoryEntry::Excluded { is_dir, .. } => *is_dir,
+            DirectoryEntry::MountPoint { .. } => true,
+            DirectoryEntry::Unreadable { is_dir, .. } => *is_dir,
         }
     }
     pub fn len(&self) -> Option<&Byteable> {
@@ -160,39 +882,115 @@ impl DirectoryEntry {
             DirectoryEntry::Rollup { len, .. } => Some(len),
             DirectoryEntry::Link { .. } => None,
             DirectoryEntry::Excluded { .. } => None,
+            DirectoryEntry::MountPoint { .. } => None,
+            DirectoryEntry::Unreadable { .. } => None,
+        }
+    }
+    /// How much the entry counts towards its folder's size, which is nothing for a hard link to a file that is
+    /// counted elsewhere. By entry count, a file is one and a folder is how many entries there are below it.
+    pub fn size(&self, measure: SizeMeasure) -> Option<Byteable> {
+        match (self, measure) {
+            (DirectoryEntry::File { .. }, SizeMeasure::EntryCount) => Some(Byteable(1)),
+            (_, SizeMeasure::EntryCount) => self.counts().map(|counts| Byteable(counts.total)),
+            (DirectoryEntry::File { hard_links: Some(HardLinks { is_counted: false, .. }), .. }, _) => {
+                Some(Byteable(0))
+            }
+            (DirectoryEntry::File { disk_len, .. }, SizeMeasure::DiskUsage) => Some(*disk_len),
+            (DirectoryEntry::Folder { disk_len, .. }, SizeMeasure::DiskUsage) => Some(*disk_len),
+            (DirectoryEntry::Rollup { disk_len, .. }, SizeMeasure::DiskUsage) => Some(*disk_len),
+            _ => self.len().copied(),
         }
     }
-    pub fn len_str(&self) -> String {
+    pub fn len_str(&self, measure: SizeMeasure) -> String {
+        let size = measure.format(self.size(measure).unwrap_or(Byteable(0)));
         match self {
-            DirectoryEntry::File { len, .. } => len.to_string(),
-            DirectoryEntry::Folder { len, .. } => len.to_string(),
-            DirectoryEntry::Rollup { len, .. } => len.to_string(),
+            DirectoryEntry::Folder { is_scanning: true, .. } => format!("{}…", size),
+            // the size of the file, in brackets as it isn't counted here
+            DirectoryEntry::File { len, disk_len, hard_links: Some(HardLinks { is_counted: false, .. }), .. }
+                if measure != SizeMeasure::EntryCount =>
+            {
+                match measure {
+                    SizeMeasure::DiskUsage => format!("({})", disk_len),
+                    _ => format!("({})", len),
+                }
+            }
+            DirectoryEntry::File { .. } | DirectoryEntry::Folder { .. } | DirectoryEntry::Rollup { .. } => size,
             DirectoryEntry::Link { .. } => "-link-".to_string(),
             DirectoryEntry::Excluded { .. } => "-excl-".to_string(),
+            DirectoryEntry::MountPoint { .. } => "-mount-".to_string(),
+            DirectoryEntry::Unreadable { is_denied: true, .. } => "-denied-".to_string(),
+            DirectoryEntry::Unreadable { .. } => "-error-".to_string(),
         }
     }
     pub fn name(&self) -> String {
-        fn get_file_name(buf: &PathBuf) -> String {
+        fn get_file_name(buf: &Path) -> String {
             buf.file_name().map_or(String::new(), |a| a.to_string_lossy().to_string())
         }
-        fn get_directory_name(buf: &PathBuf) -> String {
+        fn get_directory_name(buf: &Path) -> String {
             let mut name = get_file_name(buf);
             name.push(std::path::MAIN_SEPARATOR);
             name
         }
         match self {
-            DirectoryEntry::Excluded { path, is_dir, .. } => if *is_dir { get_directory_name(path) } else { get_file_name(path) },
+            DirectoryEntry::Excluded { path, is_dir, .. } | DirectoryEntry::Unreadable { path, is_dir, .. } => {
+                if *is_dir {
+                    get_directory_name(path)
+                } else {
+                    get_file_name(path)
+                }
+            }
             DirectoryEntry::File { path, .. } => get_file_name(path),
             DirectoryEntry::Link { path, .. } => get_file_name(path),
-            DirectoryEntry::Folder { path, .. } => {
-                get_directory_name(path)
-            }
+            DirectoryEntry::Folder { path, .. } => get_directory_name(path),
+            DirectoryEntry::MountPoint { path, .. } => get_directory_name(path),
             DirectoryEntry::Rollup { .. } => String::from(ROLLUP_NAME),
         }
     }
+    /// The name as it is listed and reported, with where a link or mount point leads, what excluded the entry, and
+    /// how many links a file has.
+    pub(crate) fn listed_name(&self) -> String {
+        match (self, self.hard_links()) {
+            (DirectoryEntry::MountPoint { file_system: Some(file_system), .. }, _) => {
+                format!("{} [{}]", self.name(), file_system)
+            }
+            (DirectoryEntry::Link { target: Some(target), .. }, _) => {
+                format!("{} → {}", self.name(), target.display())
+            }
+            (DirectoryEntry::Excluded { rule, .. }, _) => format!("{} (excluded by {})", self.name(), rule),
+            (DirectoryEntry::Folder { is_collapsed: true, .. }, _) => format!("{} (collapsed)", self.name()),
+            (_, Some(hard_links)) => format!("{} ({} links)", self.name(), hard_links.count),
+            _ => self.name(),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// `entries` with those of any rollup among them in its place.
+fn unrolled(entries: Vec<DirectoryEntry>) -> Vec<DirectoryEntry> {
+    let mut unrolled = vec![];
+    for entry in entries {
+        match entry {
+            DirectoryEntry::Rollup { entries, .. } => unrolled.extend(entries),
+            entry => unrolled.push(entry),
+        }
+    }
+    unrolled
+}
+
+fn sum_errors(entries: &[DirectoryEntry]) -> u64 { entries.iter().map(|entry| entry.errors()).sum() }
+
+fn newest(entries: &[DirectoryEntry]) -> Timestamps {
+    entries.iter().fold(Timestamps::default(), |times, entry| times.newest(entry.times()))
+}
+
+fn sum_counts(entries: &[DirectoryEntry]) -> EntryCounts {
+    entries.iter().fold(EntryCounts::default(), |counts, entry| counts.add(entry.counted()))
+}
+
+fn sum_sizes(entries: &[DirectoryEntry], measure: SizeMeasure) -> Byteable {
+    Byteable(entries.iter().map(|entry| entry.size(measure).map(|val| val.0).unwrap_or(0)).sum())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub(crate) struct Byteable(pub u64);
 
 impl fmt::Display for Byteable {
@@ -207,6 +1005,28 @@ impl fmt::Display for Byteable {
 const SCALES: [(u64, &str); 4] =
     [(1024 * 1024 * 1024 * 1024, "TB"), (1024 * 1024 * 1024, "GB"), (1024 * 1024, "MB"), (1024, "KB")];
 
+/// Parses sizes such as "50MB", "1.5 G" or "300", in the same units they are shown in.
+impl FromStr for Byteable {
+    type Err = String;
+
+    fn from_str(size: &str) -> Result<Self, Self::Err> {
+        let size = size.trim();
+        let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+        let (number, unit) = size.split_at(split);
+        let number: f64 = number.parse().map_err(|_| format!("expected a number at the start of `{}`", size))?;
+        let unit = unit.trim().to_ascii_uppercase();
+        let scale = match unit.strip_suffix('B').unwrap_or(&unit) {
+            "" => 1,
+            unit => SCALES
+                .iter()
+                .find(|(_, name)| name.starts_with(unit))
+                .map(|(scale, _)| *scale)
+                .ok_or_else(|| format!("unknown unit `{}`, expected one of B, KB, MB, GB or TB", unit))?,
+        };
+        Ok(Byteable((number * scale as f64) as u64))
+    }
+}
+
 impl Byteable {
     fn scale(&self, index: usize) -> (f64, &str) {
         if index == SCALES.len() {
@@ -239,27 +1059,102 @@ mod tests {
             assert_eq!(Byteable((1024 * 1024 * 1024) - 1).to_string(), "1023.99 MB");
             assert_eq!(Byteable(1024 * 1024 * 1024).to_string(), "1 GB");
         }
+
+        #[test]
+        fn test_byteable_parse() {
+            assert_eq!(Ok(Byteable(300)), "300".parse());
+            assert_eq!(Ok(Byteable(300)), "300B".parse());
+            assert_eq!(Ok(Byteable(50 * 1024 * 1024)), "50MB".parse());
+            assert_eq!(Ok(Byteable(3 * 512 * 1024 * 1024)), "1.5 g".parse());
+            assert_eq!(Ok(Byteable(2048)), "2K".parse());
+            assert!("MB".parse::<Byteable>().is_err());
+            assert!("5 PB".parse::<Byteable>().is_err());
+        }
+    }
+
+    mod size_measure {
+        use crate::file_analysis::file_types::SizeMeasure;
+
+        #[test]
+        fn test_cycled_reaches_every_measure() {
+            let measures: Vec<SizeMeasure> =
+                std::iter::successors(Some(SizeMeasure::EntryCount), |measure| Some(measure.cycled()))
+                    .take(4)
+                    .collect();
+            assert_eq!(
+                vec![
+                    SizeMeasure::EntryCount,
+                    SizeMeasure::ApparentSize,
+                    SizeMeasure::DiskUsage,
+                    SizeMeasure::EntryCount
+                ],
+                measures
+            );
+        }
     }
 
     mod directory_entry {
         use std::path::PathBuf;
 
-        use crate::file_analysis::file_types::DirectoryEntry;
+        use crate::file_analysis::file_types::{DirectoryEntry, SizeMeasure, Timestamps};
         use crate::file_analysis::Byteable;
 
+        #[allow(clippy::vec_init_then_push)]
         mod rollup {
             use super::*;
 
             #[test]
             fn test_rollup() {
                 let mut entries = vec![];
-                entries.push(DirectoryEntry::new_file(Byteable(0), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(1), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(2), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(3), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(4), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_folder(Byteable(5), PathBuf::new(), false, vec![], false));
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::new(), false, entries, true);
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(0),
+                    Byteable(0),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(1),
+                    Byteable(1),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(2),
+                    Byteable(2),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(3),
+                    Byteable(3),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(4),
+                    Byteable(4),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_folder(
+                    Byteable(5),
+                    Byteable(5),
+                    PathBuf::new(),
+                    false,
+                    vec![],
+                    false,
+                ));
+                let entry = DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::new(), false, entries, true);
                 let result = entry.entries().expect("no entries");
                 assert_eq!(2, result.len());
             }
@@ -267,13 +1162,55 @@ mod tests {
             #[test]
             fn test_rollup_nothing_to_roll() {
                 let mut entries = vec![];
-                entries.push(DirectoryEntry::new_file(Byteable(6), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(7), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(8), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(9), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(10), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_folder(Byteable(5), PathBuf::new(), false, vec![], false));
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::new(), false, entries, true);
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(6),
+                    Byteable(6),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(7),
+                    Byteable(7),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(8),
+                    Byteable(8),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(9),
+                    Byteable(9),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(10),
+                    Byteable(10),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_folder(
+                    Byteable(5),
+                    Byteable(5),
+                    PathBuf::new(),
+                    false,
+                    vec![],
+                    false,
+                ));
+                let entry = DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::new(), false, entries, true);
                 let result = entry.entries().expect("no entries");
                 assert_eq!(6, result.len());
             }
@@ -281,20 +1218,97 @@ mod tests {
             #[test]
             fn test_rollup_with_both() {
                 let mut entries = vec![];
-                entries.push(DirectoryEntry::new_file(Byteable(1), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(1), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(2), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(3), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(4), PathBuf::new(), false));
-
-                entries.push(DirectoryEntry::new_folder(Byteable(5), PathBuf::new(), false, vec![], false));
-
-                entries.push(DirectoryEntry::new_file(Byteable(6), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(7), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(8), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(9), PathBuf::new(), false));
-                entries.push(DirectoryEntry::new_file(Byteable(10), PathBuf::new(), false));
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::new(), false, entries, true);
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(1),
+                    Byteable(1),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(1),
+                    Byteable(1),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(2),
+                    Byteable(2),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(3),
+                    Byteable(3),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(4),
+                    Byteable(4),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+
+                entries.push(DirectoryEntry::new_folder(
+                    Byteable(5),
+                    Byteable(5),
+                    PathBuf::new(),
+                    false,
+                    vec![],
+                    false,
+                ));
+
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(6),
+                    Byteable(6),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(7),
+                    Byteable(7),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(8),
+                    Byteable(8),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(9),
+                    Byteable(9),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                entries.push(DirectoryEntry::new_file(
+                    Byteable(10),
+                    Byteable(10),
+                    PathBuf::new(),
+                    false,
+                    Timestamps::default(),
+                    None,
+                ));
+                let entry = DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::new(), false, entries, true);
                 let result = entry.entries().expect("no entries");
                 assert_eq!(7, result.len());
                 match result.first().expect("first entry exists") {
@@ -302,6 +1316,8 @@ mod tests {
                     DirectoryEntry::File { .. } => panic!("file found when expecting rollup"),
                     DirectoryEntry::Folder { .. } => panic!("folder found when expecting rollup"),
                     DirectoryEntry::Link { .. } => panic!("link found when expecting rollup"),
+                    DirectoryEntry::MountPoint { .. } => panic!("mount point found when expecting rollup"),
+                    DirectoryEntry::Unreadable { .. } => panic!("unreadable found when expecting rollup"),
                     DirectoryEntry::Rollup { len, entries, .. } => {
                         assert_eq!(11, len.0);
                         assert_eq!(5, entries.len());
@@ -310,6 +1326,324 @@ mod tests {
             }
         }
 
+        mod min_size {
+            use super::*;
+
+            #[test]
+            fn test_rollup_smaller_than_min_size() {
+                let entries = vec![
+                    DirectoryEntry::new_file(
+                        Byteable(20),
+                        Byteable(20),
+                        PathBuf::from("this/big"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_file(
+                        Byteable(2),
+                        Byteable(2),
+                        PathBuf::from("this/small"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_folder(
+                        Byteable(5),
+                        Byteable(5),
+                        PathBuf::from("this/little"),
+                        false,
+                        vec![],
+                        false,
+                    ),
+                    DirectoryEntry::new_folder(
+                        Byteable(50),
+                        Byteable(50),
+                        PathBuf::from("this/large"),
+                        false,
+                        vec![],
+                        false,
+                    ),
+                    DirectoryEntry::new_scanning_folder(PathBuf::from("this/scanning"), false),
+                ];
+                let entry = DirectoryEntry::new_folder_with_min_size(
+                    Byteable(77),
+                    Byteable(77),
+                    PathBuf::from("this"),
+                    false,
+                    entries,
+                    true,
+                    Byteable(10),
+                );
+                let result = entry.entries().expect("entries");
+                assert_eq!(4, result.len());
+                assert_eq!(PathBuf::from("this/large"), result[0].path());
+                assert_eq!(PathBuf::from("this/big"), result[1].path());
+                match &result[2] {
+                    DirectoryEntry::Rollup { len, entries, .. } => {
+                        assert_eq!(7, len.0);
+                        assert_eq!(2, entries.len());
+                    }
+                    other => panic!("expected a rollup, found {:?}", other),
+                }
+                assert_eq!(PathBuf::from("this/scanning"), result[3].path());
+            }
+
+            #[test]
+            fn test_collapse_keeps_totals() {
+                let entries = vec![
+                    DirectoryEntry::new_file(
+                        Byteable(2),
+                        Byteable(4),
+                        PathBuf::from("this/a"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_folder(
+                        Byteable(3),
+                        Byteable(4),
+                        PathBuf::from("this/that"),
+                        false,
+                        vec![],
+                        false,
+                    ),
+                ];
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(5), Byteable(8), PathBuf::from("this"), false, entries, false)
+                        .collapse();
+                assert!(entry.is_collapsed());
+                assert!(!entry.has_children());
+                assert_eq!("5 B", entry.len_str(SizeMeasure::ApparentSize));
+                assert_eq!("8 B", entry.len_str(SizeMeasure::DiskUsage));
+
+                let scanning = DirectoryEntry::new_scanning_folder(PathBuf::from("this"), false).collapse();
+                assert!(!scanning.is_collapsed());
+            }
+        }
+
+        mod counts {
+            use super::*;
+            use crate::file_analysis::file_types::EntryCounts;
+
+            #[test]
+            fn test_counts_include_folders_below() {
+                let that = DirectoryEntry::new_folder(
+                    Byteable(3),
+                    Byteable(3),
+                    PathBuf::from("this/that"),
+                    false,
+                    vec![
+                        DirectoryEntry::new_file(
+                            Byteable(1),
+                            Byteable(1),
+                            PathBuf::from("this/that/a"),
+                            false,
+                            Timestamps::default(),
+                            None,
+                        ),
+                        DirectoryEntry::new_file(
+                            Byteable(2),
+                            Byteable(2),
+                            PathBuf::from("this/that/b"),
+                            false,
+                            Timestamps::default(),
+                            None,
+                        ),
+                    ],
+                    false,
+                );
+                let entries = vec![
+                    that,
+                    DirectoryEntry::new_file(
+                        Byteable(5),
+                        Byteable(5),
+                        PathBuf::from("this/c"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_link(PathBuf::from("this/d"), None, false, false),
+                    DirectoryEntry::new_scanning_folder(PathBuf::from("this/e"), false),
+                ];
+                let mut entry =
+                    DirectoryEntry::new_folder(Byteable(8), Byteable(8), PathBuf::from("this"), false, entries, true);
+                assert_eq!(Some(EntryCounts { files: 3, dirs: 2, total: 6 }), entry.counts());
+                assert_eq!("6…", entry.len_str(SizeMeasure::EntryCount));
+
+                let scanned = vec![DirectoryEntry::new_file(
+                    Byteable(1),
+                    Byteable(1),
+                    PathBuf::from("this/e/f"),
+                    false,
+                    Timestamps::default(),
+                    None,
+                )];
+                let e = DirectoryEntry::new_folder(
+                    Byteable(1),
+                    Byteable(1),
+                    PathBuf::from("this/e"),
+                    false,
+                    scanned,
+                    false,
+                );
+                assert!(entry.replace(e, SizeMeasure::EntryCount));
+                assert_eq!(Some(EntryCounts { files: 4, dirs: 2, total: 7 }), entry.counts());
+                assert_eq!(PathBuf::from("this/that"), entry.entries().expect("entries")[0].path());
+            }
+        }
+
+        mod extensions {
+            use std::path::Path;
+
+            use super::*;
+            use crate::file_analysis::file_types::FileTotal;
+            use crate::file_analysis::mock_utils::file;
+
+            #[test]
+            fn test_extensions_and_largest_files() {
+                let videos = DirectoryEntry::new_folder(
+                    Byteable(60),
+                    Byteable(60),
+                    PathBuf::from("this/videos"),
+                    false,
+                    vec![file(40, "this/videos/a.mp4"), file(20, "this/videos/b.MP4")],
+                    false,
+                );
+                let entries = vec![videos, file(30, "this/c.mp4"), file(5, "this/d.log"), file(1, "this/e")];
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(96), Byteable(96), PathBuf::from("this"), false, entries, true);
+                let extensions = &entry.extensions().expect("a folder").0;
+                assert_eq!(Some(&FileTotal { len: 90, disk_len: 90, files: 3 }), extensions.get("mp4"));
+                assert_eq!(Some(&FileTotal { len: 5, disk_len: 5, files: 1 }), extensions.get("log"));
+                assert_eq!(Some(&FileTotal { len: 1, disk_len: 1, files: 1 }), extensions.get(""));
+
+                let largest: Vec<&Path> = entry
+                    .largest_files("mp4", SizeMeasure::ApparentSize, 2)
+                    .into_iter()
+                    .map(|file| file.path())
+                    .collect();
+                assert_eq!(vec![Path::new("this/videos/a.mp4"), Path::new("this/c.mp4")], largest);
+                assert!(entry.largest_files("iso", SizeMeasure::ApparentSize, 2).is_empty());
+                // what is below a collapsed folder isn't kept, but its totals are
+                let totals = entry.extensions();
+                assert_eq!(totals, entry.collapse().extensions());
+            }
+
+            #[test]
+            fn test_largest_entries() {
+                let folder = |len: u64, path: &str, entries: Vec<DirectoryEntry>, is_hidden: bool| {
+                    DirectoryEntry::new_folder(
+                        Byteable(len),
+                        Byteable(len),
+                        PathBuf::from(path),
+                        is_hidden,
+                        entries,
+                        false,
+                    )
+                };
+                let cache = folder(
+                    70,
+                    "this/.cache",
+                    vec![folder(70, "this/.cache/deep", vec![file(70, "this/.cache/deep/blob")], false)],
+                    true,
+                );
+                let logs = folder(
+                    15,
+                    "this/videos/logs",
+                    vec![file(10, "this/videos/logs/a.log"), file(5, "this/videos/logs/b.log")],
+                    false,
+                );
+                let videos = folder(
+                    60,
+                    "this/videos",
+                    vec![file(40, "this/videos/a.mp4"), file(20, "this/videos/b.mp4"), logs],
+                    false,
+                );
+                let entries = vec![cache, videos, file(30, "this/c.iso"), file(1, "this/e")];
+                let entry = DirectoryEntry::new_folder(
+                    Byteable(161),
+                    Byteable(161),
+                    PathBuf::from("this"),
+                    false,
+                    entries,
+                    true,
+                );
+                let paths = |entries: Vec<&DirectoryEntry>| -> Vec<PathBuf> {
+                    entries.into_iter().map(|entry| entry.path().to_path_buf()).collect()
+                };
+
+                let (files, folders) = entry.largest_entries(SizeMeasure::ApparentSize, 3, false);
+                assert_eq!(
+                    vec![
+                        PathBuf::from("this/videos/a.mp4"),
+                        PathBuf::from("this/c.iso"),
+                        PathBuf::from("this/videos/b.mp4")
+                    ],
+                    paths(files)
+                );
+                assert_eq!(vec![PathBuf::from("this/videos/logs")], paths(folders));
+                let (files, folders) = entry.largest_entries(SizeMeasure::ApparentSize, 1, true);
+                assert_eq!(vec![PathBuf::from("this/.cache/deep/blob")], paths(files));
+                assert_eq!(vec![PathBuf::from("this/.cache/deep")], paths(folders));
+            }
+        }
+
+        mod times {
+            use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+            use super::*;
+            use crate::file_analysis::mock_utils::file_with_times;
+
+            fn file(len: u64, path: &str, modified: u64) -> DirectoryEntry {
+                let modified = UNIX_EPOCH.checked_add(Duration::from_secs(modified));
+                file_with_times(len, path, Timestamps { modified, accessed: None, changed: modified })
+            }
+
+            fn at(seconds: u64) -> SystemTime { UNIX_EPOCH + Duration::from_secs(seconds) }
+
+            #[test]
+            fn test_folder_has_newest_times() {
+                let old = DirectoryEntry::new_folder(
+                    Byteable(30),
+                    Byteable(30),
+                    PathBuf::from("this/old"),
+                    false,
+                    vec![file(10, "this/old/a", 100), file(20, "this/old/b", 200)],
+                    false,
+                );
+                let entries = vec![old, file(5, "this/c", 1000), file(1, "this/d", 50)];
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(36), Byteable(36), PathBuf::from("this"), false, entries, true);
+                assert_eq!(Some(at(1000)), entry.times().modified);
+                assert_eq!(Some(at(1000)), entry.times().changed);
+                assert_eq!(None, entry.times().accessed);
+                let old = entry.find(&PathBuf::from("this/old")).expect("a folder");
+                assert_eq!(Some(at(200)), old.times().modified);
+            }
+
+            #[test]
+            fn test_stale_size() {
+                let old = DirectoryEntry::new_folder(
+                    Byteable(30),
+                    Byteable(30),
+                    PathBuf::from("this/old"),
+                    false,
+                    vec![file(10, "this/old/a", 100), file(20, "this/old/b", 200)],
+                    false,
+                );
+                let entries = vec![old, file(5, "this/c", 1000), file(1, "this/d", 50)];
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(36), Byteable(36), PathBuf::from("this"), false, entries, true);
+                assert_eq!(36, entry.stale_size(at(2000), SizeMeasure::ApparentSize));
+                assert_eq!(31, entry.stale_size(at(500), SizeMeasure::ApparentSize));
+                assert_eq!(11, entry.stale_size(at(150), SizeMeasure::ApparentSize));
+                assert_eq!(2, entry.stale_size(at(150), SizeMeasure::EntryCount));
+                assert_eq!(0, entry.stale_size(at(10), SizeMeasure::ApparentSize));
+            }
+        }
+
+        #[allow(clippy::vec_init_then_push)]
         mod find {
             use std::path::MAIN_SEPARATOR;
 
@@ -317,15 +1651,24 @@ mod tests {
 
             #[test]
             fn test_find_self() {
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::from("this"), false, vec![], true);
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("this"), false, vec![], true);
                 assert_eq!(&entry, entry.find(&PathBuf::from("this")).expect("to find self"));
             }
 
             #[test]
             fn test_find_self_with_more_than_one_same_name() {
                 let mut entries = vec![];
-                entries.push(DirectoryEntry::new_folder(Byteable(0), PathBuf::from("this"), false, vec![], false));
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::from("this"), false, entries, true);
+                entries.push(DirectoryEntry::new_folder(
+                    Byteable(0),
+                    Byteable(0),
+                    PathBuf::from("this"),
+                    false,
+                    vec![],
+                    false,
+                ));
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("this"), false, entries, true);
                 assert_eq!(&entry, entry.find(&PathBuf::from("this")).expect("to find self"));
             }
 
@@ -333,13 +1676,15 @@ mod tests {
             fn test_find_other_with_more_than_one_same_name() {
                 let mut entries = vec![];
                 entries.push(DirectoryEntry::new_folder(
+                    Byteable(10),
                     Byteable(10),
                     PathBuf::from(format!("this{}this", MAIN_SEPARATOR)),
                     false,
                     vec![],
                     false,
                 ));
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::from("this"), false, entries, true);
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("this"), false, entries, true);
                 assert_eq!(
                     10,
                     entry
@@ -354,22 +1699,273 @@ mod tests {
             #[test]
             fn test_find_self_with_more_than_one_different_name() {
                 let mut entries = vec![];
-                entries.push(DirectoryEntry::new_folder(Byteable(0), PathBuf::from("that"), false, vec![], false));
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::from("this"), false, entries, true);
+                entries.push(DirectoryEntry::new_folder(
+                    Byteable(0),
+                    Byteable(0),
+                    PathBuf::from("that"),
+                    false,
+                    vec![],
+                    false,
+                ));
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("this"), false, entries, true);
                 assert_eq!(&entry, entry.find(&PathBuf::from("this")).expect("to find self"));
             }
 
             #[test]
             fn test_find_other_with_more_than_one_different_name() {
                 let mut entries = vec![];
-                let that = DirectoryEntry::new_folder(Byteable(0), PathBuf::from("that"), false, vec![], false);
+                let that =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("that"), false, vec![], false);
                 entries.push(that);
-                let entry = DirectoryEntry::new_folder(Byteable(0), PathBuf::from("this"), false, entries, true);
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("this"), false, entries, true);
                 assert_eq!(
                     format!("that{}", MAIN_SEPARATOR),
                     entry.find(&PathBuf::from("that")).expect("to find other").name()
                 );
             }
+
+            #[test]
+            fn test_find_folder_smaller_than_rollup() {
+                let entries = vec![
+                    DirectoryEntry::new_file(
+                        Byteable(6),
+                        Byteable(6),
+                        PathBuf::from("this/a"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_file(
+                        Byteable(6),
+                        Byteable(6),
+                        PathBuf::from("this/b"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_folder(
+                        Byteable(10),
+                        Byteable(10),
+                        PathBuf::from("this/that"),
+                        false,
+                        vec![],
+                        false,
+                    ),
+                ];
+                let entry =
+                    DirectoryEntry::new_folder(Byteable(22), Byteable(22), PathBuf::from("this"), false, entries, true);
+                assert_eq!(
+                    10,
+                    entry.find(&PathBuf::from("this/that")).expect("to find other").len().expect("a length").0
+                );
+            }
+        }
+
+        mod replace {
+            use super::*;
+            use crate::file_analysis::file_types::EntryCounts;
+            use crate::file_analysis::mock_utils::file;
+
+            #[test]
+            fn test_replace_recalculates_ancestors() {
+                let scanning = vec![
+                    DirectoryEntry::new_file(
+                        Byteable(1),
+                        Byteable(1),
+                        PathBuf::from("this/a"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_scanning_folder(PathBuf::from("this/that"), false),
+                ];
+                let mut entry =
+                    DirectoryEntry::new_folder(Byteable(1), Byteable(1), PathBuf::from("this"), false, scanning, true);
+                assert!(entry.is_scanning());
+
+                let scanned = vec![DirectoryEntry::new_file(
+                    Byteable(5),
+                    Byteable(5),
+                    PathBuf::from("this/that/b"),
+                    false,
+                    Timestamps::default(),
+                    None,
+                )];
+                let that = DirectoryEntry::new_folder(
+                    Byteable(5),
+                    Byteable(5),
+                    PathBuf::from("this/that"),
+                    false,
+                    scanned,
+                    false,
+                );
+                assert!(entry.replace(that, SizeMeasure::ApparentSize));
+
+                assert!(!entry.is_scanning());
+                assert_eq!(6, entry.len().expect("a length").0);
+                let entries = entry.entries().expect("entries");
+                assert_eq!(2, entries.len());
+                assert_eq!(PathBuf::from("this/that"), entries[0].path());
+                assert!(matches!(entries[1], DirectoryEntry::Rollup { .. }));
+            }
+
+            #[test]
+            fn test_replace_rescanned_folder_shrinks_ancestors() {
+                let b = DirectoryEntry::new_folder(
+                    Byteable(100),
+                    Byteable(100),
+                    PathBuf::from("this/a/b"),
+                    false,
+                    vec![file(100, "this/a/b/big")],
+                    false,
+                );
+                let a = DirectoryEntry::new_folder(
+                    Byteable(110),
+                    Byteable(110),
+                    PathBuf::from("this/a"),
+                    false,
+                    vec![b, file(10, "this/a/c")],
+                    false,
+                );
+                let mut entry = DirectoryEntry::new_folder(
+                    Byteable(110),
+                    Byteable(110),
+                    PathBuf::from("this"),
+                    false,
+                    vec![a],
+                    true,
+                );
+
+                // as if "big" had been deleted and "b" scanned again
+                let rescanned = DirectoryEntry::new_folder(
+                    Byteable(0),
+                    Byteable(0),
+                    PathBuf::from("this/a/b"),
+                    false,
+                    vec![],
+                    false,
+                );
+                assert!(entry.replace(rescanned, SizeMeasure::ApparentSize));
+
+                assert_eq!(10, entry.len().expect("a length").0);
+                let a = entry.find(&PathBuf::from("this/a")).expect("a folder");
+                assert_eq!(10, a.len().expect("a length").0);
+                assert_eq!(Some(EntryCounts { files: 1, dirs: 1, total: 2 }), a.counts());
+            }
+
+            #[test]
+            fn test_replace_missing() {
+                let mut entry =
+                    DirectoryEntry::new_folder(Byteable(0), Byteable(0), PathBuf::from("this"), false, vec![], true);
+                let other =
+                    DirectoryEntry::new_folder(Byteable(5), Byteable(5), PathBuf::from("that"), false, vec![], false);
+                assert!(!entry.replace(other, SizeMeasure::ApparentSize));
+                assert_eq!(0, entry.len().expect("a length").0);
+            }
+        }
+
+        mod insert {
+            use std::path::Path;
+
+            use super::*;
+            use crate::file_analysis::mock_utils::file;
+
+            fn tree() -> DirectoryEntry {
+                let that = DirectoryEntry::new_folder(
+                    Byteable(6),
+                    Byteable(6),
+                    PathBuf::from("this/that"),
+                    false,
+                    vec![file(5, "this/that/b"), file(1, "this/that/c")],
+                    false,
+                );
+                DirectoryEntry::new_folder(
+                    Byteable(16),
+                    Byteable(16),
+                    PathBuf::from("this"),
+                    false,
+                    vec![file(10, "this/a"), that],
+                    true,
+                )
+            }
+
+            #[test]
+            fn test_insert_replaces_or_adds() {
+                let mut entry = tree();
+                // in the rollup of "that"
+                assert!(entry.insert(file(50, "this/that/b"), SizeMeasure::ApparentSize));
+                assert_eq!(61, entry.len().expect("a length").0);
+                assert!(entry.insert(file(3, "this/that/d"), SizeMeasure::ApparentSize));
+                assert_eq!(64, entry.len().expect("a length").0);
+                let that = entry.find(&PathBuf::from("this/that")).expect("a folder");
+                assert_eq!(54, that.len().expect("a length").0);
+                assert_eq!(3, that.counts().expect("counts").files);
+                assert_eq!(PathBuf::from("this/that"), entry.entries().expect("entries")[0].path());
+
+                assert!(!entry.insert(file(1, "elsewhere/e"), SizeMeasure::ApparentSize));
+                assert_eq!(64, entry.len().expect("a length").0);
+            }
+
+            #[test]
+            fn test_remove() {
+                let mut entry = tree();
+                assert!(entry.remove(&PathBuf::from("this/that/c"), SizeMeasure::ApparentSize));
+                assert_eq!(15, entry.len().expect("a length").0);
+                assert!(entry.find(&PathBuf::from("this/that/c")).is_none());
+                assert!(entry.remove(&PathBuf::from("this/that"), SizeMeasure::ApparentSize));
+                assert_eq!(10, entry.len().expect("a length").0);
+                assert_eq!(vec![Path::new("this")], entry.folders());
+
+                assert!(!entry.remove(&PathBuf::from("this/that"), SizeMeasure::ApparentSize));
+            }
+        }
+
+        mod sort_by {
+            use super::*;
+
+            #[test]
+            fn test_sort_by_disk_usage() {
+                let entries = vec![
+                    DirectoryEntry::new_file(
+                        Byteable(100),
+                        Byteable(0),
+                        PathBuf::from("this/sparse"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_file(
+                        Byteable(10),
+                        Byteable(4096),
+                        PathBuf::from("this/small"),
+                        false,
+                        Timestamps::default(),
+                        None,
+                    ),
+                    DirectoryEntry::new_folder(
+                        Byteable(0),
+                        Byteable(0),
+                        PathBuf::from("this/empty"),
+                        false,
+                        vec![],
+                        false,
+                    ),
+                ];
+                let mut entry = DirectoryEntry::new_folder(
+                    Byteable(110),
+                    Byteable(4096),
+                    PathBuf::from("this"),
+                    false,
+                    entries,
+                    true,
+                );
+                assert_eq!(PathBuf::from("this/sparse"), entry.entries().expect("entries")[0].path());
+                entry.sort_by(SizeMeasure::DiskUsage);
+                assert_eq!(PathBuf::from("this/small"), entry.entries().expect("entries")[0].path());
+                assert_eq!("4 KB", entry.len_str(SizeMeasure::DiskUsage));
+            }
         }
     }
 }
@@ -1,18 +1,23 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::path::PathBuf;
+use std::io;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use mockall::{mock, Sequence};
 
 use super::file_system_proxy_traits::*;
+use super::file_types::{Byteable, DirectoryEntry, SizeMeasure, Timestamps};
+use super::owners::Owner;
 
 mock! {
     pub(crate) MyReadDirProxy {}
-    impl ReadDirProxy for MyReadDirProxy {
-        fn path(&mut self) -> PathBuf;
-    }
+    impl ReadDirProxy for MyReadDirProxy {}
     impl Iterator for MyReadDirProxy {
-        type Item = Result<Box<dyn DirPathEntryProxy>, Box<dyn Error>>;
-        fn next(&mut self) -> Option<Result<Box<dyn DirPathEntryProxy>, Box<dyn Error>>>;
+        type Item = DirEntryResult;
+        fn next(&mut self) -> Option<DirEntryResult>;
     }
 }
 
@@ -25,11 +30,18 @@ pub(crate) fn set_expect(
     expect_read_dir(num_directories, num_files, &mut mock_file_operations, dir.clone(), &mut seq_read_dir);
     mock_file_operations.expect_metadata().returning(|_| {
         let mut metadata = MockMetadataProxy::new();
+        metadata.expect_is_dir().return_const(false);
         metadata.expect_len().return_const(1024 * 1024_u64);
+        metadata.expect_disk_len().return_const(1024 * 1024_u64);
+        metadata.expect_links().return_const(1_u64);
+        metadata.expect_modified().return_const(None);
+        metadata.expect_accessed().return_const(None);
+        metadata.expect_changed().return_const(None);
+        metadata.expect_owner().return_const(None);
 
         #[cfg(target_os = "windows")]
         metadata.expect_file_attributes().return_const(0_u32);
-        
+
         Ok(Box::new(metadata))
     });
     Ok((dir, mock_file_operations))
@@ -41,7 +53,6 @@ fn expect_read_dir(
 ) {
     mock_file_operations.expect_read_dir().times(1).in_sequence(seq_read_dir).returning(move |_dir| {
         let mut mock_read_dir = MockMyReadDirProxy::new();
-        // mock_read_dir.expect_path().times(1).returning(|| PathBuf::from("test"));
         let mut seq = Sequence::new();
         for _i in 0..num_directories {
             expect_read_dir_next(true, &mut mock_read_dir, &mut seq);
@@ -71,3 +82,230 @@ fn expect_read_dir_next(is_dir: bool, mock_read_dir: &mut MockMyReadDirProxy, se
         Some(Ok(Box::new(entry)))
     });
 }
+
+/// Describes a file tree for `set_expect_tree`, with names relative to the folder containing them.
+pub(crate) enum MockEntry {
+    File(&'static str, u64),
+    /// A file with the given len, last modified the given number of seconds after the epoch.
+    Modified(&'static str, u64, u64),
+    /// A file with the given len, owned by the given user and group ids.
+    Owned(&'static str, u64, u32, u32),
+    /// A file with the given contents.
+    Contents(&'static str, String),
+    /// A hard link to the file with the given inode number, with the given len.
+    HardLink(&'static str, u64, u64),
+    Folder(&'static str, Vec<MockEntry>),
+    /// A folder with another file system, of the given type, mounted on it.
+    Mount(&'static str, &'static str, Vec<MockEntry>),
+    /// A symlink to the given path, which is relative to the directory the tree is in rather than the symlink.
+    Symlink(&'static str, &'static str),
+    /// A folder that can't be read, as permission is denied.
+    Unreadable(&'static str),
+    /// An entry deleted after its folder was read, so its file type can't be.
+    Vanished(&'static str),
+}
+
+#[derive(Clone, Copy, Default)]
+struct MockMetadata {
+    len: u64,
+    is_dir: bool,
+    inode: u64,
+    device: u64,
+    modified: Option<SystemTime>,
+    owner: Option<Owner>,
+}
+
+#[derive(Clone, Copy)]
+enum MockFileType {
+    File,
+    Folder,
+    Symlink,
+    Vanished,
+}
+
+/// Mocks a file system containing `entries` under the directory "current", where unlike `set_expect` every entry has
+/// its own path.
+pub(crate) fn set_expect_tree(entries: Vec<MockEntry>) -> (PathBuf, MockFileSystemProxy) {
+    #[derive(Default)]
+    struct MockTree {
+        folders: HashMap<PathBuf, Vec<(PathBuf, MockFileType)>>,
+        metadata: HashMap<PathBuf, MockMetadata>,
+        mounts: HashMap<PathBuf, &'static str>,
+        symlinks: HashMap<PathBuf, PathBuf>,
+        unreadable: HashSet<PathBuf>,
+        contents: HashMap<PathBuf, String>,
+        // hard links are given their inode, other entries a new one from here
+        next_inode: u64,
+    }
+
+    fn add_folder(path: PathBuf, device: u64, entries: Vec<MockEntry>, tree: &mut MockTree) {
+        let mut children = vec![];
+        for entry in entries {
+            tree.next_inode += 1;
+            let inode = tree.next_inode;
+            match entry {
+                MockEntry::File(name, len) => {
+                    children.push((path.join(name), MockFileType::File));
+                    let metadata = MockMetadata { len, is_dir: false, inode, device, ..MockMetadata::default() };
+                    tree.metadata.insert(path.join(name), metadata);
+                }
+                MockEntry::Modified(name, len, seconds) => {
+                    children.push((path.join(name), MockFileType::File));
+                    let modified = UNIX_EPOCH.checked_add(Duration::from_secs(seconds));
+                    let metadata = MockMetadata { len, is_dir: false, inode, device, modified, owner: None };
+                    tree.metadata.insert(path.join(name), metadata);
+                }
+                MockEntry::Owned(name, len, uid, gid) => {
+                    children.push((path.join(name), MockFileType::File));
+                    let owner = Some(Owner { uid, gid });
+                    let metadata = MockMetadata { len, is_dir: false, inode, device, modified: None, owner };
+                    tree.metadata.insert(path.join(name), metadata);
+                }
+                MockEntry::Contents(name, contents) => {
+                    children.push((path.join(name), MockFileType::File));
+                    let len = contents.len() as u64;
+                    let metadata = MockMetadata { len, is_dir: false, inode, device, ..MockMetadata::default() };
+                    tree.metadata.insert(path.join(name), metadata);
+                    tree.contents.insert(path.join(name), contents);
+                }
+                MockEntry::HardLink(name, len, inode) => {
+                    children.push((path.join(name), MockFileType::File));
+                    let metadata = MockMetadata { len, is_dir: false, inode, device, ..MockMetadata::default() };
+                    tree.metadata.insert(path.join(name), metadata);
+                }
+                MockEntry::Folder(name, entries) => {
+                    children.push((path.join(name), MockFileType::Folder));
+                    add_folder(path.join(name), device, entries, tree);
+                }
+                MockEntry::Mount(name, file_system, entries) => {
+                    children.push((path.join(name), MockFileType::Folder));
+                    tree.mounts.insert(path.join(name), file_system);
+                    add_folder(path.join(name), device + 1, entries, tree);
+                }
+                MockEntry::Symlink(name, target) => {
+                    children.push((path.join(name), MockFileType::Symlink));
+                    tree.symlinks.insert(path.join(name), PathBuf::from(target));
+                }
+                MockEntry::Unreadable(name) => {
+                    children.push((path.join(name), MockFileType::Folder));
+                    tree.unreadable.insert(path.join(name));
+                }
+                MockEntry::Vanished(name) => children.push((path.join(name), MockFileType::Vanished)),
+            }
+        }
+        tree.next_inode += 1;
+        let metadata = MockMetadata { len: 0, is_dir: true, inode: tree.next_inode, device, ..MockMetadata::default() };
+        tree.metadata.insert(path.clone(), metadata);
+        tree.folders.insert(path, children);
+    }
+
+    let dir = PathBuf::from("current");
+    let mut tree = MockTree { next_inode: 1000, ..MockTree::default() };
+    add_folder(dir.clone(), 1, entries, &mut tree);
+    let MockTree { folders, metadata: files, mounts, symlinks, unreadable, contents, .. } = tree;
+    let mut links: HashMap<u64, u64> = HashMap::new();
+    for file in files.values() {
+        *links.entry(file.inode).or_default() += 1;
+    }
+
+    // the path with every symlink in it replaced by its target, as far as is possible
+    let symlinks = Arc::new(symlinks);
+    let resolve = move |path: &Path| {
+        let mut resolved = PathBuf::new();
+        for component in path.components() {
+            resolved.push(component);
+            for _ in 0..40 {
+                match symlinks.get(&resolved) {
+                    Some(target) => resolved = target.clone(),
+                    None => break,
+                }
+            }
+        }
+        resolved
+    };
+    let resolve = Arc::new(resolve);
+
+    let mut mock_file_operations = MockFileSystemProxy::new();
+    let resolve_dir = resolve.clone();
+    mock_file_operations.expect_read_dir().returning(move |dir| {
+        if unreadable.contains(dir) {
+            return Err(Box::new(io::Error::from(io::ErrorKind::PermissionDenied)));
+        }
+        let children = folders.get(&resolve_dir(dir)).cloned().unwrap_or_default();
+        // the children of a symlinked folder are under the symlink
+        let mut children = children
+            .into_iter()
+            .map(|(path, file_type)| (dir.join(path.file_name().expect("a name")), file_type))
+            .collect::<Vec<(PathBuf, MockFileType)>>()
+            .into_iter();
+        let mut mock_read_dir = MockMyReadDirProxy::new();
+        mock_read_dir.expect_next().returning(move || {
+            children.next().map(|(path, file_type)| {
+                let mut entry = MockDirPathEntryProxy::new();
+                entry.expect_path().return_const(path);
+                entry.expect_file_type().returning(move || {
+                    if let MockFileType::Vanished = file_type {
+                        return Err(io::Error::from(io::ErrorKind::NotFound));
+                    }
+                    let mut mock_file_type = MockFileTypeProxy::new();
+                    mock_file_type.expect_is_dir().return_const(matches!(file_type, MockFileType::Folder));
+                    mock_file_type.expect_is_symlink().return_const(matches!(file_type, MockFileType::Symlink));
+                    Ok(Box::new(mock_file_type))
+                });
+                Ok(Box::new(entry) as Box<dyn DirPathEntryProxy>)
+            })
+        });
+        Ok(Box::new(mock_read_dir))
+    });
+    let resolve_metadata = resolve.clone();
+    mock_file_operations.expect_metadata().returning(move |path| {
+        let mut metadata = MockMetadataProxy::new();
+        let MockMetadata { len, is_dir, inode, device, modified, owner } =
+            files.get(&resolve_metadata(path)).copied().unwrap_or_default();
+        metadata.expect_is_dir().return_const(is_dir);
+        metadata.expect_len().return_const(len);
+        // as if allocated in 4 KiB blocks
+        metadata.expect_disk_len().return_const(len.div_ceil(4096) * 4096);
+        metadata.expect_links().return_const(links.get(&inode).copied().unwrap_or(1));
+        metadata.expect_file_id().return_const(FileId { device, inode });
+        metadata.expect_modified().return_const(modified);
+        metadata.expect_accessed().return_const(modified);
+        metadata.expect_changed().return_const(modified);
+        metadata.expect_owner().return_const(owner);
+
+        #[cfg(target_os = "windows")]
+        metadata.expect_file_attributes().return_const(0_u32);
+
+        Ok(Box::new(metadata))
+    });
+    mock_file_operations
+        .expect_file_system_type()
+        .returning(move |path| mounts.get(path).map(|file_system| file_system.to_string()));
+    let resolve_link = resolve.clone();
+    mock_file_operations.expect_read_link().returning(move |path| {
+        let target = resolve_link(path);
+        (target != path).then_some(target)
+    });
+    let resolve_open = resolve.clone();
+    mock_file_operations.expect_open().returning(move |path| match contents.get(&resolve_open(path)) {
+        Some(contents) => Ok(Box::new(Cursor::new(contents.clone().into_bytes()))),
+        None => Err(io::Error::from(io::ErrorKind::NotFound)),
+    });
+    mock_file_operations.expect_canonicalize().returning(move |path| Some(resolve(path)));
+    (dir, mock_file_operations)
+}
+
+/// A file of `len` bytes by either measure, hidden if its name starts with a dot.
+pub(crate) fn file(len: u64, path: &str) -> DirectoryEntry { file_with_times(len, path, Timestamps::default()) }
+
+pub(crate) fn file_with_times(len: u64, path: &str, times: Timestamps) -> DirectoryEntry {
+    let path = PathBuf::from(path);
+    let is_hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    DirectoryEntry::new_file(Byteable(len), Byteable(len), path, is_hidden, times, None)
+}
+
+/// A folder as large as what is in it.
+pub(crate) fn folder(path: &str, entries: Vec<DirectoryEntry>, is_root: bool) -> DirectoryEntry {
+    let len = entries.iter().map(|entry| entry.size(SizeMeasure::ApparentSize).map_or(0, |size| size.0)).sum();
+    DirectoryEntry::new_folder(Byteable(len), Byteable(len), PathBuf::from(path), false, entries, is_root)
+}
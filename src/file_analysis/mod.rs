@@ -1,83 +1,414 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use lazy_static::lazy_static;
-use regex::{Error, RegexSet};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 
-use file_system_proxy_traits::FileSystemProxy;
-use file_types::Byteable;
+use exclusions::Exclusions;
+use file_system_proxy_traits::{DirPathEntryProxy, FileId, FileSystemProxy, MetadataProxy};
+use file_types::{Byteable, HardLinks, Timestamps};
+use progress::ScanProgress;
 
-use crate::file_analysis::file_types::DirectoryEntry;
+use crate::file_analysis::file_types::{DirectoryEntry, SizeMeasure};
 
+pub(crate) mod diff;
+pub(crate) mod duplicates;
+pub(crate) mod exclusions;
+pub(crate) mod extensions;
 pub(crate) mod file_system_proxy_traits;
 pub(crate) mod file_types;
+pub(crate) mod ncdu;
+pub(crate) mod owners;
+pub(crate) mod progress;
+pub(crate) mod snapshot;
+pub(crate) mod watch;
 
-lazy_static! {
-    pub(crate) static ref EXCL_PATTERNS: Result<RegexSet, Error> =
-        RegexSet::new([r"^/proc$", r"^/sys$", r"^/mnt/\w*$"]);
+/// `threads` is the size of the work-stealing pool used to scan, where 0 means one thread per logical CPU.
+/// Folders less than `live_depth` below the root are sent as they are listed, with placeholders for their
+/// sub-folders, rather than when everything below them has been scanned, see `read_fs`. With `one_file_system`
+/// folders on other file systems than the one scanned are shown as mount points rather than scanned. Entries matching
+/// `exclusions` are shown as excluded rather than scanned. With `follow_symlinks` links to anything outside the
+/// scanned tree are scanned as if they were what they link to, each target at most once. Folders more than `max_depth`
+/// below the scanned one, and those smaller than `min_size` by `measure`, are collapsed to their totals so that the
+/// tree kept in memory stays small, and entries smaller than `min_size` are rolled up.
+#[derive(Default)]
+pub(crate) struct ScanOptions {
+    pub(crate) threads: usize,
+    pub(crate) live_depth: usize,
+    pub(crate) one_file_system: bool,
+    pub(crate) exclusions: Exclusions,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) min_size: Byteable,
+    pub(crate) measure: SizeMeasure,
 }
 
-pub(crate) fn read_fs(current_dir: PathBuf, file_operations: &impl FileSystemProxy) -> DirectoryEntry {
-    populate_tree(file_operations, current_dir, true)
+/// Scans the tree from `current_dir` down, sending the result to `on_update`. With a `live_depth` of 0 that is a
+/// single update with the whole tree, otherwise the first update is the root folder and each of those that follow
+/// should be spliced into it with `DirectoryEntry::replace`. Progress is reported to, and the scan can be cancelled
+/// through, `progress`. `current_dir` needn't be the `is_root` of the tree, e.g. when entering a mount point, when
+/// the files with more than one hard link in `counted_links` are those already counted elsewhere in the tree.
+pub(crate) fn read_fs(
+    current_dir: PathBuf, is_root: bool, file_operations: &impl FileSystemProxy, options: &ScanOptions,
+    counted_links: HashSet<FileId>, progress: &ScanProgress, on_update: &(dyn Fn(DirectoryEntry) + Sync),
+) {
+    let scanner = Scanner::new(&current_dir, file_operations, options, counted_links, progress, on_update);
+    match ThreadPoolBuilder::new().num_threads(options.threads).build() {
+        Ok(pool) => pool.install(|| scanner.scan(current_dir, is_root, 0)),
+        Err(_) => scanner.scan(current_dir, is_root, 0),
+    }
+}
+
+/// Reads the entry at `path`, which has been created or modified since the tree containing it was scanned, scanning
+/// everything below it if it is a folder, but without following symlinks. Files with more than one hard link in
+/// `counted_links` are counted elsewhere in the tree already. Returns none if it no longer exists.
+pub(crate) fn read_entry(
+    path: PathBuf, file_operations: &impl FileSystemProxy, options: &ScanOptions, counted_links: HashSet<FileId>,
+    progress: &ScanProgress,
+) -> Option<DirectoryEntry> {
+    let folder = path.parent()?;
+    let scanner = Scanner {
+        followed_root: None,
+        ..Scanner::new(folder, file_operations, options, counted_links, progress, &|_| {})
+    };
+    let is_symlink = file_operations.read_link(&path).is_some();
+    let is_dir = !is_symlink && file_operations.metadata(&path).ok()?.is_dir();
+    scanner.visit_entry(scanner.child_entry(path, is_dir, is_symlink, None), false, 0)
 }
 
-fn is_excluded(path: &PathBuf) -> bool {
-    EXCL_PATTERNS.as_ref().map(|regex| regex.is_match(&path.display().to_string())).unwrap_or(false)
+struct ChildEntry {
+    path: PathBuf,
+    is_dir: bool,
+    is_symlink: bool,
+    /// The rule that excluded it, if any.
+    excluded_by: Option<String>,
+    is_mount_point: bool,
+    /// Why its file type couldn't be read.
+    error: Option<io::Error>,
 }
 
-fn populate_tree(file_operations: &impl FileSystemProxy, current_dir: PathBuf, is_root: bool) -> DirectoryEntry {
-    if let Ok(directory_entries) = file_operations.read_dir(&current_dir) {
-        let mut len = 0_u64;
-        let mut entries = vec![];
-        for entry in directory_entries {
-            let entry = entry.expect("error in getting entry");
-            let entry_path = entry.path();
-            let file_type = entry.file_type().expect("error getting file type");
-            if is_excluded(&entry_path) {
-                let hidden = is_hidden(file_operations, &entry_path);
-                entries.push(DirectoryEntry::new_excluded(entry_path, file_type.is_dir(), hidden, is_root));
-            } else if file_type.is_symlink() {
-                let hidden = is_hidden(file_operations, &entry_path);
-                entries.push(DirectoryEntry::new_link(entry_path, false, hidden));
-            } else if file_type.is_dir() {
-                let child = populate_tree(file_operations, entry_path, false);
-                len += child.len().map(|val| val.0).unwrap_or(0);
-                entries.push(child);
-            } else if let Ok(metadata) = file_operations.metadata(&entry_path) {
-                len += metadata.len();
-                let len = Byteable(metadata.len());
-                let hidden = is_hidden(file_operations, &entry_path);
-                entries.push(DirectoryEntry::new_file(len, entry_path, hidden));
+impl ChildEntry {
+    fn is_traversable(&self) -> bool {
+        self.is_dir && !self.is_symlink && self.excluded_by.is_none() && !self.is_mount_point && self.error.is_none()
+    }
+}
+
+struct Scanner<'a, T: FileSystemProxy> {
+    file_operations: &'a T,
+    progress: &'a ScanProgress,
+    on_update: &'a (dyn Fn(DirectoryEntry) + Sync),
+    live_depth: usize,
+    exclusions: &'a Exclusions,
+    /// The device of the scanned file system, when staying on it.
+    device: Option<u64>,
+    /// The canonical path of the scanned tree, when following symlinks.
+    followed_root: Option<PathBuf>,
+    /// Files with more than one hard link that have already been counted.
+    seen_links: Mutex<HashSet<FileId>>,
+    /// The canonical paths of the targets of the symlinks that have been followed, so that nothing is scanned twice,
+    /// or forever in a cycle.
+    followed_links: Mutex<Vec<PathBuf>>,
+    max_depth: Option<usize>,
+    min_size: Byteable,
+    /// What folders are smaller than `min_size` by.
+    measure: SizeMeasure,
+}
+
+impl<'a, T: FileSystemProxy> Scanner<'a, T> {
+    /// For scanning the tree below `current_dir`, where the files in `counted_links` are counted elsewhere.
+    fn new(
+        current_dir: &Path, file_operations: &'a T, options: &'a ScanOptions, counted_links: HashSet<FileId>,
+        progress: &'a ScanProgress, on_update: &'a (dyn Fn(DirectoryEntry) + Sync),
+    ) -> Self {
+        let device = if options.one_file_system {
+            file_operations.metadata(current_dir).ok().map(|metadata| metadata.file_id().device)
+        } else {
+            None
+        };
+        Scanner {
+            file_operations,
+            progress,
+            on_update,
+            live_depth: options.live_depth,
+            exclusions: &options.exclusions,
+            device,
+            followed_root: if options.follow_symlinks { file_operations.canonicalize(current_dir) } else { None },
+            seen_links: Mutex::new(counted_links),
+            followed_links: Mutex::new(vec![]),
+            max_depth: options.max_depth,
+            min_size: options.min_size,
+            measure: options.measure.bytes(),
+        }
+    }
+
+    fn scan(&self, current_dir: PathBuf, is_root: bool, depth: usize) {
+        // collapsed folders aren't shown until they are complete
+        if depth >= self.live_depth || self.is_collapsed(depth) {
+            (self.on_update)(self.populate_tree(current_dir, is_root, depth));
+            return;
+        }
+        match self.read_children(&current_dir) {
+            Ok(children) => {
+                let (directories, others): (Vec<ChildEntry>, Vec<ChildEntry>) =
+                    children.into_iter().partition(ChildEntry::is_traversable);
+                let mut entries: Vec<DirectoryEntry> =
+                    others.into_par_iter().filter_map(|child| self.visit_entry(child, is_root, depth)).collect();
+                for directory in directories.iter() {
+                    self.progress.add_entry(0);
+                    let hidden = is_hidden(self.file_operations, &directory.path);
+                    entries.push(DirectoryEntry::new_scanning_folder(directory.path.clone(), hidden));
+                }
+                (self.on_update)(self.new_folder(current_dir, entries, is_root));
+                directories.into_par_iter().for_each(|directory| self.scan(directory.path, false, depth + 1));
+            }
+            Err(error) => (self.on_update)(self.new_unreadable_folder(current_dir, error.as_ref(), is_root)),
+        }
+    }
+
+    /// `depth` is how far `current_dir` is below the scanned folder.
+    fn populate_tree(&self, current_dir: PathBuf, is_root: bool, depth: usize) -> DirectoryEntry {
+        match self.read_children(&current_dir) {
+            Ok(children) => {
+                let entries: Vec<DirectoryEntry> =
+                    children.into_par_iter().filter_map(|child| self.visit_entry(child, is_root, depth)).collect();
+                let folder = self.new_folder(current_dir, entries, is_root);
+                let is_small = folder.size(self.measure).is_some_and(|size| size.0 < self.min_size.0);
+                if !is_root && (self.is_collapsed(depth) || is_small) {
+                    folder.collapse()
+                } else {
+                    folder
+                }
+            }
+            Err(error) => self.new_unreadable_folder(current_dir, error.as_ref(), is_root),
+        }
+    }
+
+    fn is_collapsed(&self, depth: usize) -> bool { self.max_depth.is_some_and(|max_depth| depth > max_depth) }
+
+    /// There are no children once the scan is cancelled.
+    fn read_children(&self, current_dir: &Path) -> Result<Vec<ChildEntry>, Box<dyn Error>> {
+        if self.progress.is_cancelled() {
+            return Ok(vec![]);
+        }
+        self.progress.enter_directory(current_dir);
+        // the directory iterator can't be shared between threads, so drain it here and visit the children in
+        // parallel
+        Ok(self
+            .file_operations
+            .read_dir(current_dir)?
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(self.new_child_entry(entry.as_ref())),
+                // without an entry there is no path to show it with, so it is only counted
+                Err(_) => {
+                    self.progress.add_error();
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn new_child_entry(&self, entry: &dyn DirPathEntryProxy) -> ChildEntry {
+        let path = entry.path();
+        let (is_dir, is_symlink, error) = match entry.file_type() {
+            Ok(file_type) => (file_type.is_dir(), file_type.is_symlink(), None),
+            Err(error) => (false, false, Some(error)),
+        };
+        self.child_entry(path, is_dir, is_symlink, error)
+    }
+
+    fn child_entry(&self, path: PathBuf, is_dir: bool, is_symlink: bool, error: Option<io::Error>) -> ChildEntry {
+        ChildEntry {
+            excluded_by: self.exclusions.excluded_by(&path).map(str::to_string),
+            is_mount_point: is_dir && !is_symlink && self.is_mount_point(&path),
+            path,
+            is_dir,
+            is_symlink,
+            error,
+        }
+    }
+
+    /// The root of the tree is shown as a folder containing what couldn't be read, as only folders can be shown.
+    fn new_unreadable_folder(&self, path: PathBuf, error: &(dyn Error + 'static), is_root: bool) -> DirectoryEntry {
+        self.progress.add_error();
+        let hidden = is_hidden(self.file_operations, &path);
+        let unreadable = DirectoryEntry::new_unreadable(path.clone(), error, true, hidden);
+        if is_root {
+            DirectoryEntry::new_folder(Byteable(0), Byteable(0), path, hidden, vec![unreadable], true)
+        } else {
+            unreadable
+        }
+    }
+
+    fn new_folder(&self, current_dir: PathBuf, entries: Vec<DirectoryEntry>, is_root: bool) -> DirectoryEntry {
+        let sum =
+            |measure| entries.iter().map(|entry: &DirectoryEntry| entry.size(measure).map_or(0, |val| val.0)).sum();
+        let (len, disk_len) = (sum(SizeMeasure::ApparentSize), sum(SizeMeasure::DiskUsage));
+        let hidden = is_hidden(self.file_operations, &current_dir);
+        DirectoryEntry::new_folder_with_min_size(
+            Byteable(len),
+            Byteable(disk_len),
+            current_dir,
+            hidden,
+            entries,
+            is_root,
+            self.min_size,
+        )
+    }
+
+    /// `depth` is that of the folder containing `child`.
+    fn visit_entry(&self, child: ChildEntry, is_root: bool, depth: usize) -> Option<DirectoryEntry> {
+        let ChildEntry { path: entry_path, is_dir, is_symlink, excluded_by, is_mount_point, error } = child;
+        if let Some(error) = error {
+            self.progress.add_error();
+            let hidden = is_hidden(self.file_operations, &entry_path);
+            Some(DirectoryEntry::new_unreadable(entry_path, &error, false, hidden))
+        } else if let Some(rule) = excluded_by {
+            self.progress.add_entry(0);
+            let hidden = is_hidden(self.file_operations, &entry_path);
+            Some(DirectoryEntry::new_excluded(entry_path, is_dir, hidden, is_root, rule))
+        } else if is_symlink {
+            self.follow_link(&entry_path, depth + 1).or_else(|| {
+                self.progress.add_entry(0);
+                let hidden = is_hidden(self.file_operations, &entry_path);
+                let target = self.file_operations.read_link(&entry_path);
+                Some(DirectoryEntry::new_link(entry_path, target, false, hidden))
+            })
+        } else if is_mount_point {
+            self.progress.add_entry(0);
+            let hidden = is_hidden(self.file_operations, &entry_path);
+            let file_system = self.file_operations.file_system_type(&entry_path);
+            Some(DirectoryEntry::new_mount_point(entry_path, file_system, hidden))
+        } else if is_dir {
+            self.progress.add_entry(0);
+            Some(self.populate_tree(entry_path, false, depth + 1))
+        } else {
+            match self.file_operations.metadata(&entry_path) {
+                Ok(metadata) => Some(self.new_file(entry_path, metadata.as_ref())),
+                Err(error) => {
+                    self.progress.add_error();
+                    let hidden = is_hidden(self.file_operations, &entry_path);
+                    Some(DirectoryEntry::new_unreadable(entry_path, error.as_ref(), false, hidden))
+                }
+            }
+        }
+    }
+
+    fn new_file(&self, path: PathBuf, metadata: &dyn MetadataProxy) -> DirectoryEntry {
+        let hidden = is_hidden(self.file_operations, &path);
+        let (len, disk_len) = (Byteable(metadata.len()), Byteable(metadata.disk_len()));
+        let times =
+            Timestamps { modified: metadata.modified(), accessed: metadata.accessed(), changed: metadata.changed() };
+        let owner = metadata.owner();
+        match self.hard_links(metadata) {
+            Some(hard_links) => {
+                self.progress.add_entry(if hard_links.is_counted { len.0 } else { 0 });
+                DirectoryEntry::new_hard_link(len, disk_len, path, hidden, hard_links, times, owner)
+            }
+            None => {
+                self.progress.add_entry(len.0);
+                DirectoryEntry::new_file(len, disk_len, path, hidden, times, owner)
             }
         }
-        let hidden = is_hidden(file_operations, &current_dir);
-        DirectoryEntry::new_folder(Byteable(len), current_dir, hidden, entries, is_root)
-    } else {
-        DirectoryEntry::new_folder(Byteable(0), current_dir, false, vec![], is_root)
+    }
+
+    /// Scans what the symlink at `path` links to, unless not following symlinks, or it is in the scanned tree, where
+    /// it's counted anyway, or contains the scanned tree, or is in or contains a target already followed.
+    fn follow_link(&self, path: &Path, depth: usize) -> Option<DirectoryEntry> {
+        let root = self.followed_root.as_ref()?;
+        let target = self.file_operations.canonicalize(path)?;
+        let overlaps = |other: &PathBuf| target.starts_with(other) || other.starts_with(&target);
+        if overlaps(root) {
+            return None;
+        }
+        let metadata = self.file_operations.metadata(path).ok()?;
+        let is_new = self.followed_links.lock().map(|mut followed| {
+            let is_new = !followed.iter().any(overlaps);
+            if is_new {
+                followed.push(target.clone());
+            }
+            is_new
+        });
+        if !is_new.unwrap_or(false) {
+            return None;
+        }
+        if metadata.is_dir() {
+            self.progress.add_entry(0);
+            Some(self.populate_tree(path.to_path_buf(), false, depth))
+        } else {
+            Some(self.new_file(path.to_path_buf(), metadata.as_ref()))
+        }
+    }
+
+    fn is_mount_point(&self, path: &Path) -> bool {
+        self.device.is_some_and(|device| {
+            self.file_operations.metadata(path).map(|metadata| metadata.file_id().device != device).unwrap_or(false)
+        })
+    }
+
+    /// Only the first link found to a file is counted, whichever thread finds it.
+    fn hard_links(&self, metadata: &dyn MetadataProxy) -> Option<HardLinks> {
+        let count = metadata.links();
+        (count > 1).then(|| {
+            let id = metadata.file_id();
+            let is_counted = self.seen_links.lock().map(|mut seen| seen.insert(id)).unwrap_or(true);
+            HardLinks { count, is_counted, id }
+        })
     }
 }
 
-fn is_hidden(_file_operations: &impl FileSystemProxy, current_dir: &PathBuf) -> bool {
+fn is_hidden(_file_operations: &impl FileSystemProxy, current_dir: &Path) -> bool {
     #[cfg(target_os = "windows")]
-    return _file_operations.metadata(&current_dir).map(|m| (m.file_attributes() & 0b_10) == 0b_10).unwrap_or(true);
+    return _file_operations.metadata(current_dir).map(|m| (m.file_attributes() & 0b_10) == 0b_10).unwrap_or(true);
 
     #[cfg(not(target_os = "windows"))]
-    return current_dir.file_name().and_then(|name| name.to_str()).map(|name| name.starts_with(".")).unwrap_or(false);
+    return current_dir.file_name().and_then(|name| name.to_str()).map(|name| name.starts_with('.')).unwrap_or(false);
 }
 
 #[cfg(test)]
-mod mock_utils;
+pub(crate) mod mock_utils;
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
+    use std::collections::HashSet;
     use std::error::Error;
-    use std::path::MAIN_SEPARATOR;
+    use std::path::{PathBuf, MAIN_SEPARATOR};
+    use std::sync::Mutex;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::file_analysis::duplicates::find_duplicates;
+    use crate::file_analysis::exclusions::Exclusions;
+    use crate::file_analysis::file_system_proxy_traits::MockFileSystemProxy;
+    use crate::file_analysis::file_types::{DirectoryEntry, EntryCounts, FileTotal, SizeMeasure};
+    use crate::file_analysis::mock_utils::MockEntry;
+    use crate::file_analysis::progress::ScanProgress;
+    use crate::file_analysis::{mock_utils, read_fs, Byteable, ScanOptions};
 
-    use crate::file_analysis::{mock_utils, read_fs};
+    pub(crate) fn read_tree(
+        dir: PathBuf, file_operations: &MockFileSystemProxy, options: ScanOptions, progress: &ScanProgress,
+    ) -> DirectoryEntry {
+        let tree: Mutex<Option<DirectoryEntry>> = Mutex::new(None);
+        read_fs(dir, true, file_operations, &options, HashSet::new(), progress, &|update| {
+            let mut tree = tree.lock().expect("lock");
+            match tree.as_mut() {
+                Some(root) => assert!(root.replace(update, SizeMeasure::ApparentSize), "update for unknown path"),
+                None => *tree = Some(update),
+            }
+        });
+        tree.into_inner().expect("lock").expect("a tree")
+    }
+
+    fn read(dir: PathBuf, file_operations: &MockFileSystemProxy) -> DirectoryEntry {
+        read_tree(dir, file_operations, ScanOptions::default(), &ScanProgress::default())
+    }
 
     #[test]
     fn test_run_with_1_file() -> Result<(), Box<dyn Error>> {
         let (dir, mock_file_operations) = mock_utils::set_expect(0, 1)?;
-        let entry = read_fs(dir, &mock_file_operations);
+        let entry = read(dir, &mock_file_operations);
         assert_eq!(entry.len().expect("a len").0, 1024 * 1024_u64);
         if let Some(entries) = entry.entries() {
             let mut iter = entries.iter();
@@ -96,7 +427,7 @@ mod tests {
     #[test]
     fn test_run_with_1_directory() -> Result<(), Box<dyn Error>> {
         let (dir, mock_file_operations) = mock_utils::set_expect(1, 0)?;
-        let entry = read_fs(dir, &mock_file_operations);
+        let entry = read(dir, &mock_file_operations);
         assert_eq!(entry.len().expect("a len").0, 0_u64);
         if let Some(entries) = entry.entries() {
             let mut iter = entries.iter();
@@ -116,7 +447,7 @@ mod tests {
     #[test]
     fn test_run_with_2_directory() -> Result<(), Box<dyn Error>> {
         let (dir, mock_file_operations) = mock_utils::set_expect(2, 0)?;
-        let entry = read_fs(dir, &mock_file_operations);
+        let entry = read(dir, &mock_file_operations);
         assert_eq!(entry.len().expect("a len").0, 0_u64);
         if let Some(entries) = entry.entries() {
             let mut iter = entries.iter();
@@ -142,7 +473,7 @@ mod tests {
     #[test]
     fn test_run_with_1_directory_and_1_file() -> Result<(), Box<dyn Error>> {
         let (dir, mock_file_operations) = mock_utils::set_expect(1, 1)?;
-        let entry = read_fs(dir, &mock_file_operations);
+        let entry = read(dir, &mock_file_operations);
         assert_eq!(entry.len().expect("a len").0, 1024 * 1024_u64);
         if let Some(entries) = entry.entries() {
             let mut iter = entries.iter();
@@ -168,7 +499,7 @@ mod tests {
     #[test]
     fn test_run_with_2_directory_and_2_file() -> Result<(), Box<dyn Error>> {
         let (dir, mock_file_operations) = mock_utils::set_expect(2, 2)?;
-        let entry = read_fs(dir, &mock_file_operations);
+        let entry = read(dir, &mock_file_operations);
         assert_eq!(entry.len().expect("a len").0, 1024 * 1024_u64 * 2_u64);
         if let Some(entries) = entry.entries() {
             let mut iter = entries.iter();
@@ -205,4 +536,479 @@ mod tests {
             panic!("None should be some")
         }
     }
+
+    #[test]
+    fn test_run_with_many_threads_matches_single_thread() -> Result<(), Box<dyn Error>> {
+        let (dir, mock_file_operations) = mock_utils::set_expect(2, 2)?;
+        let single = read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 1, live_depth: 0, ..ScanOptions::default() },
+            &ScanProgress::default(),
+        );
+        let (dir, mock_file_operations) = mock_utils::set_expect(2, 2)?;
+        let parallel = read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 4, live_depth: 0, ..ScanOptions::default() },
+            &ScanProgress::default(),
+        );
+        assert_eq!(single, parallel);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_reports_progress() -> Result<(), Box<dyn Error>> {
+        let (dir, mock_file_operations) = mock_utils::set_expect(2, 2)?;
+        let progress = ScanProgress::default();
+        read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 1, live_depth: 0, ..ScanOptions::default() },
+            &progress,
+        );
+        let report = progress.report();
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.bytes, Byteable(1024 * 1024_u64 * 2_u64));
+        assert_eq!(report.errors, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_cancelled_does_not_read() {
+        let mut mock_file_operations = MockFileSystemProxy::new();
+        mock_file_operations.expect_read_dir().never();
+        let progress = ScanProgress::default();
+        progress.cancel();
+        let entry = read_tree(
+            PathBuf::from("current"),
+            &mock_file_operations,
+            ScanOptions { threads: 1, live_depth: 0, ..ScanOptions::default() },
+            &progress,
+        );
+        assert_eq!(entry.len().expect("a len").0, 0_u64);
+        assert!(!entry.has_children());
+    }
+
+    #[test]
+    fn test_run_live_matches_complete() {
+        fn tree() -> Vec<MockEntry> {
+            vec![
+                MockEntry::File("a", 1),
+                MockEntry::File("b", 2),
+                MockEntry::Folder(
+                    "c",
+                    vec![MockEntry::File("d", 4), MockEntry::Folder("e", vec![MockEntry::Folder("f", vec![])])],
+                ),
+                MockEntry::Folder("g", vec![MockEntry::File("h", 8), MockEntry::File("i", 16)]),
+            ]
+        }
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree());
+        let complete = read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 1, live_depth: 0, ..ScanOptions::default() },
+            &ScanProgress::default(),
+        );
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree());
+        let live = read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 4, live_depth: 2, ..ScanOptions::default() },
+            &ScanProgress::default(),
+        );
+        assert_eq!(complete, live);
+        assert!(!live.is_scanning());
+        assert_eq!(live.len().expect("a len").0, 31);
+    }
+
+    #[test]
+    fn test_run_counts_hard_links_once() {
+        let tree = vec![
+            MockEntry::File("a", 1),
+            MockEntry::HardLink("b", 10, 7),
+            MockEntry::Folder("c", vec![MockEntry::HardLink("d", 10, 7)]),
+        ];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let progress = ScanProgress::default();
+        let entry = read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 2, live_depth: 1, ..ScanOptions::default() },
+            &progress,
+        );
+        assert_eq!(entry.len().expect("a len").0, 11);
+        assert_eq!(progress.report().bytes.0, 11);
+        let links: Vec<&DirectoryEntry> =
+            ["current/b", "current/c/d"].iter().map(|path| entry.find(&PathBuf::from(path)).expect("a link")).collect();
+        assert!(links.iter().all(|link| link.hard_links().is_some_and(|hard_links| hard_links.count == 2)));
+        assert_eq!(
+            links.iter().filter(|link| link.hard_links().is_some_and(|hard_links| hard_links.is_counted)).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_rescan_counts_hard_links_once() {
+        let tree = vec![
+            MockEntry::File("a", 1),
+            MockEntry::HardLink("b", 10, 7),
+            MockEntry::Folder("c", vec![MockEntry::HardLink("d", 10, 7), MockEntry::File("e", 2)]),
+        ];
+        let options = || ScanOptions { threads: 1, live_depth: 1, ..ScanOptions::default() };
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let complete = read_tree(dir.clone(), &mock_file_operations, options(), &ScanProgress::default());
+        let mut rescanned = read_tree(dir.clone(), &mock_file_operations, options(), &ScanProgress::default());
+
+        // as the browser does
+        let folder = dir.join("c");
+        let placeholder = DirectoryEntry::new_scanning_folder(folder.clone(), false);
+        assert!(rescanned.replace(placeholder, SizeMeasure::ApparentSize));
+        let counted_links = rescanned.counted_links(Some(&folder));
+        let rescanned = Mutex::new(rescanned);
+        read_fs(folder, false, &mock_file_operations, &options(), counted_links, &ScanProgress::default(), &|update| {
+            assert!(rescanned.lock().expect("lock").replace(update, SizeMeasure::ApparentSize))
+        });
+
+        let rescanned = rescanned.into_inner().expect("lock");
+        assert_eq!(13, rescanned.len().expect("a len").0);
+        assert_eq!(complete, rescanned);
+    }
+
+    #[test]
+    fn test_run_finds_duplicates() {
+        let tree = vec![
+            MockEntry::Contents("a", "same".to_string()),
+            MockEntry::HardLink("b", 10, 7),
+            MockEntry::Folder("c", vec![MockEntry::HardLink("d", 10, 7), MockEntry::Contents("e", "same".to_string())]),
+        ];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let options = ScanOptions { threads: 1, live_depth: 1, ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        // hard links to the same file aren't copies of it
+        let mut candidates = entry.duplicate_candidates();
+        candidates.sort();
+        assert_eq!(3, candidates.len());
+        assert_eq!((PathBuf::from("current/a"), 4), candidates[0]);
+
+        let sets = find_duplicates(candidates, &mock_file_operations, 1);
+        assert_eq!(1, sets.len());
+        assert_eq!(vec![PathBuf::from("current/a"), PathBuf::from("current/c/e")], sets[0].paths);
+    }
+
+    #[test]
+    fn test_run_one_file_system() {
+        fn tree() -> Vec<MockEntry> {
+            vec![
+                MockEntry::File("a", 1),
+                MockEntry::Mount("b", "tmpfs", vec![MockEntry::File("c", 2), MockEntry::Folder("d", vec![])]),
+            ]
+        }
+        let options = ScanOptions { threads: 1, live_depth: 1, one_file_system: true, ..ScanOptions::default() };
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree());
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        assert_eq!(entry.len().expect("a len").0, 1);
+        let mount_point = entry.find(&PathBuf::from("current/b")).expect("a mount point");
+        assert_eq!(
+            &DirectoryEntry::new_mount_point(PathBuf::from("current/b"), Some("tmpfs".to_string()), false),
+            mount_point
+        );
+
+        // entering the mount point scans it as usual
+        let (_, mock_file_operations) = mock_utils::set_expect_tree(tree());
+        let options = ScanOptions { threads: 1, live_depth: 1, one_file_system: true, ..ScanOptions::default() };
+        let tree: Mutex<Option<DirectoryEntry>> = Mutex::new(None);
+        read_fs(
+            PathBuf::from("current/b"),
+            false,
+            &mock_file_operations,
+            &options,
+            HashSet::new(),
+            &ScanProgress::default(),
+            &|update| {
+                let mut tree = tree.lock().expect("lock");
+                match tree.as_mut() {
+                    Some(root) => assert!(root.replace(update, SizeMeasure::ApparentSize)),
+                    None => *tree = Some(update),
+                }
+            },
+        );
+        let mounted = tree.into_inner().expect("lock").expect("a tree");
+        assert!(!mounted.is_root());
+        assert_eq!(mounted.len().expect("a len").0, 2);
+    }
+
+    #[test]
+    fn test_run_excluded_by_rule() {
+        let tree = vec![MockEntry::File("a.log", 1), MockEntry::Folder("b", vec![MockEntry::File("c", 2)])];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let exclusions = Exclusions::new(vec!["*.log".to_string(), "current/b".to_string()], &[]).expect("valid rules");
+        let options = ScanOptions { threads: 1, live_depth: 1, exclusions, ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        assert_eq!(entry.len().expect("a len").0, 0);
+        assert_eq!(
+            Some(&DirectoryEntry::new_excluded(
+                PathBuf::from("current/a.log"),
+                false,
+                false,
+                true,
+                "*.log".to_string()
+            )),
+            entry.find(&PathBuf::from("current/a.log"))
+        );
+        assert_eq!(
+            Some(&DirectoryEntry::new_excluded(PathBuf::from("current/b"), true, false, true, "current/b".to_string())),
+            entry.find(&PathBuf::from("current/b"))
+        );
+    }
+
+    #[test]
+    fn test_run_follow_symlinks() {
+        fn tree() -> Vec<MockEntry> {
+            vec![
+                MockEntry::Folder(
+                    "inner",
+                    vec![
+                        MockEntry::File("a", 1),
+                        MockEntry::Symlink("out", "current/outside"),
+                        MockEntry::Symlink("out2", "current/outside"),
+                        MockEntry::Symlink("up", "current/inner"),
+                        MockEntry::Symlink("top", "current"),
+                    ],
+                ),
+                MockEntry::Folder(
+                    "outside",
+                    vec![MockEntry::File("b", 10), MockEntry::Symlink("back", "current/outside")],
+                ),
+            ]
+        }
+        let (_, mock_file_operations) = mock_utils::set_expect_tree(tree());
+        let dir = PathBuf::from("current/inner");
+        let entry = read_tree(dir.clone(), &mock_file_operations, ScanOptions::default(), &ScanProgress::default());
+        assert_eq!(entry.len().expect("a len").0, 1);
+
+        let (_, mock_file_operations) = mock_utils::set_expect_tree(tree());
+        let options = ScanOptions { follow_symlinks: true, ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        assert_eq!(entry.len().expect("a len").0, 11);
+        let followed = ["current/inner/out", "current/inner/out2"]
+            .iter()
+            .filter_map(|path| entry.find(&PathBuf::from(path)))
+            .filter(|entry| entry.is_dir())
+            .collect::<Vec<&DirectoryEntry>>();
+        assert_eq!(1, followed.len());
+        let back = followed[0].path().join("back");
+        assert_eq!(
+            Some(&DirectoryEntry::new_link(back.clone(), Some(PathBuf::from("current/outside")), false, false)),
+            entry.find(&back)
+        );
+        assert_eq!(
+            Some(&DirectoryEntry::new_link(
+                PathBuf::from("current/inner/up"),
+                Some(PathBuf::from("current/inner")),
+                false,
+                false
+            )),
+            entry.find(&PathBuf::from("current/inner/up"))
+        );
+    }
+
+    #[test]
+    fn test_run_follow_nested_symlinks_once() {
+        let tree = vec![
+            MockEntry::Folder(
+                "inner",
+                vec![
+                    MockEntry::File("a", 1),
+                    MockEntry::Symlink("data", "current/outside"),
+                    MockEntry::Symlink("sub", "current/outside/sub"),
+                ],
+            ),
+            MockEntry::Folder(
+                "outside",
+                vec![MockEntry::File("b", 10), MockEntry::Folder("sub", vec![MockEntry::File("c", 100)])],
+            ),
+        ];
+        let (_, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let options = ScanOptions { threads: 1, follow_symlinks: true, ..ScanOptions::default() };
+        let entry = read_tree(PathBuf::from("current/inner"), &mock_file_operations, options, &ScanProgress::default());
+        assert_eq!(111, entry.len().expect("a len").0);
+        assert!(entry.find(&PathBuf::from("current/inner/data")).is_some_and(DirectoryEntry::is_dir));
+        assert!(!entry.find(&PathBuf::from("current/inner/sub")).is_some_and(DirectoryEntry::is_dir));
+    }
+
+    #[test]
+    fn test_run_records_unreadable() {
+        let tree = vec![MockEntry::File("a", 1), MockEntry::Unreadable("b"), MockEntry::Vanished("c")];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let progress = ScanProgress::default();
+        let options = ScanOptions { threads: 1, live_depth: 1, ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &progress);
+        assert_eq!(2, entry.errors());
+        assert_eq!(2, progress.report().errors);
+        assert!(matches!(
+            entry.find(&PathBuf::from("current/b")),
+            Some(DirectoryEntry::Unreadable { is_denied: true, is_dir: true, .. })
+        ));
+        assert!(matches!(
+            entry.find(&PathBuf::from("current/c")),
+            Some(DirectoryEntry::Unreadable { is_denied: false, is_dir: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_run_unreadable_root() {
+        let mut mock_file_operations = MockFileSystemProxy::new();
+        mock_file_operations
+            .expect_read_dir()
+            .returning(|_| Err(Box::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied))));
+        let entry = read_tree(
+            PathBuf::from("current"),
+            &mock_file_operations,
+            ScanOptions::default(),
+            &ScanProgress::default(),
+        );
+        assert!(entry.is_root());
+        assert_eq!(1, entry.errors());
+        assert_eq!("-denied-", entry.entries().expect("entries")[0].len_str(SizeMeasure::ApparentSize));
+    }
+
+    #[test]
+    fn test_run_sums_disk_usage() {
+        let tree = vec![MockEntry::File("a", 1), MockEntry::Folder("b", vec![MockEntry::File("c", 5000)])];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let entry = read_tree(
+            dir,
+            &mock_file_operations,
+            ScanOptions { threads: 1, live_depth: 1, ..ScanOptions::default() },
+            &ScanProgress::default(),
+        );
+        assert_eq!(entry.size(SizeMeasure::ApparentSize), Some(Byteable(5001)));
+        assert_eq!(entry.size(SizeMeasure::DiskUsage), Some(Byteable(4096 + 8192)));
+        let folder = entry.find(&PathBuf::from("current/b")).expect("a folder");
+        assert_eq!(folder.size(SizeMeasure::DiskUsage), Some(Byteable(8192)));
+    }
+
+    #[test]
+    fn test_run_counts_entries() {
+        let tree = vec![
+            MockEntry::File("a", 1),
+            MockEntry::Folder(
+                "b",
+                vec![MockEntry::File("c", 1), MockEntry::Folder("d", vec![MockEntry::File("e", 1)])],
+            ),
+        ];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let options = ScanOptions { threads: 1, live_depth: 1, max_depth: Some(1), ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        assert_eq!(Some(EntryCounts { files: 3, dirs: 2, total: 5 }), entry.counts());
+        let collapsed = entry.find(&PathBuf::from("current/b/d")).expect("a collapsed folder");
+        assert_eq!(Some(EntryCounts { files: 1, dirs: 0, total: 1 }), collapsed.counts());
+    }
+
+    #[test]
+    fn test_run_keeps_newest_modified() {
+        let tree = vec![
+            MockEntry::Modified("a", 1, 100),
+            MockEntry::Folder("b", vec![MockEntry::Modified("c", 1, 300), MockEntry::File("d", 1)]),
+        ];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let options = ScanOptions { threads: 1, live_depth: 1, ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        let at = |seconds| UNIX_EPOCH.checked_add(Duration::from_secs(seconds));
+        assert_eq!(at(300), entry.times().modified);
+        assert_eq!(at(300), entry.times().accessed);
+        let a = entry.find(&PathBuf::from("current/a")).expect("a file");
+        assert_eq!(at(100), a.times().modified);
+        let d = entry.find(&PathBuf::from("current/b/d")).expect("a file");
+        assert_eq!(None, d.times().modified);
+    }
+
+    #[test]
+    fn test_run_totals_owners() {
+        let tree = vec![
+            MockEntry::Owned("a", 10, 1000, 100),
+            MockEntry::Folder(
+                "b",
+                vec![MockEntry::Owned("c", 5, 0, 0), MockEntry::Folder("d", vec![MockEntry::Owned("e", 1, 1000, 0)])],
+            ),
+            MockEntry::File("f", 7),
+        ];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let options = ScanOptions { threads: 1, live_depth: 1, max_depth: Some(1), ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        let owners = entry.owners().expect("a folder");
+        assert_eq!(Some(&FileTotal { len: 11, disk_len: 8192, files: 2 }), owners.users.get(&1000));
+        assert_eq!(Some(&FileTotal { len: 5, disk_len: 4096, files: 1 }), owners.users.get(&0));
+        assert_eq!(Some(&FileTotal { len: 6, disk_len: 8192, files: 2 }), owners.groups.get(&0));
+        assert_eq!(Some(&FileTotal { len: 10, disk_len: 4096, files: 1 }), owners.groups.get(&100));
+        // files without an owner aren't counted against anyone
+        assert_eq!(2, owners.users.len());
+        let collapsed = entry.find(&PathBuf::from("current/b/d")).expect("a collapsed folder");
+        let owners = collapsed.owners().expect("a folder");
+        assert_eq!(Some(&FileTotal { len: 1, disk_len: 4096, files: 1 }), owners.users.get(&1000));
+    }
+
+    #[test]
+    fn test_run_max_depth() {
+        for live_depth in [0, 1, 3] {
+            let tree = vec![
+                MockEntry::File("a", 1),
+                MockEntry::Folder(
+                    "b",
+                    vec![MockEntry::Folder(
+                        "c",
+                        vec![MockEntry::Folder("d", vec![MockEntry::File("e", 100)]), MockEntry::File("f", 10)],
+                    )],
+                ),
+            ];
+            let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+            let options = ScanOptions { threads: 1, live_depth, max_depth: Some(1), ..ScanOptions::default() };
+            let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+            assert_eq!(entry.size(SizeMeasure::ApparentSize), Some(Byteable(111)));
+            assert!(!entry.find(&PathBuf::from("current/b")).expect("a folder").is_collapsed());
+            let collapsed = entry.find(&PathBuf::from("current/b/c")).expect("a collapsed folder");
+            assert!(collapsed.is_collapsed());
+            assert_eq!(collapsed.size(SizeMeasure::ApparentSize), Some(Byteable(110)));
+            assert_eq!(None, entry.find(&PathBuf::from("current/b/c/d")));
+        }
+    }
+
+    #[test]
+    fn test_run_min_size() {
+        let tree = vec![
+            MockEntry::File("big", 5000),
+            MockEntry::File("small", 1),
+            MockEntry::Folder("tiny", vec![MockEntry::File("x", 2)]),
+            MockEntry::Folder("large", vec![MockEntry::File("y", 2000), MockEntry::File("z", 3)]),
+        ];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        let options = ScanOptions { threads: 1, min_size: Byteable(100), ..ScanOptions::default() };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        assert_eq!(entry.size(SizeMeasure::ApparentSize), Some(Byteable(7006)));
+        let entries = entry.entries().expect("entries");
+        assert_eq!(3, entries.len());
+        assert_eq!(PathBuf::from("current/big"), entries[0].path());
+        assert_eq!(PathBuf::from("current/large"), entries[1].path());
+        assert!(matches!(&entries[2], DirectoryEntry::Rollup { len: Byteable(3), .. }));
+        assert!(entry.find(&PathBuf::from("current/tiny")).expect("a collapsed folder").is_collapsed());
+        assert_eq!(None, entry.find(&PathBuf::from("current/tiny/x")));
+        assert!(!entry.find(&PathBuf::from("current/large")).expect("a folder").is_collapsed());
+    }
+
+    #[test]
+    fn test_run_min_size_by_disk_usage() {
+        let tree = vec![MockEntry::File("big", 5000), MockEntry::Folder("tiny", vec![MockEntry::File("x", 2)])];
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(tree);
+        // 2 bytes take up a whole block
+        let options = ScanOptions {
+            threads: 1,
+            min_size: Byteable(100),
+            measure: SizeMeasure::DiskUsage,
+            ..ScanOptions::default()
+        };
+        let entry = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        assert!(!entry.find(&PathBuf::from("current/tiny")).expect("a folder").is_collapsed());
+        assert!(entry.find(&PathBuf::from("current/tiny/x")).is_some());
+    }
 }
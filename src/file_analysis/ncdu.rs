@@ -0,0 +1,372 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::file_analysis::file_system_proxy_traits::FileId;
+use crate::file_analysis::file_types::{Byteable, DirectoryEntry, HardLinks, SizeMeasure, Timestamps};
+use crate::file_analysis::owners::Owner;
+use crate::file_analysis::snapshot::Snapshot;
+
+/// The major version of ncdu's export format, which changes when older readers can't read it, and the minor version
+/// written, that of ncdu 1.9 onwards.
+const MAJOR_VERSION: u64 = 1;
+const MINOR_VERSION: u64 = 2;
+/// The file type bits of a mode, and those of a symlink.
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// What ncdu exports about an entry. Only `name` is always there, and most flags only when they are set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Info {
+    name: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    asize: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    dsize: u64,
+    /// Only when it differs from its folder's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dev: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ino: Option<u64>,
+    /// Whether it has more than one hard link.
+    #[serde(default, skip_serializing_if = "is_false")]
+    hlnkc: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nlink: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    read_error: bool,
+    /// Why it wasn't scanned, "pattern", "otherfs", "kernfs" or "frmlnk".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    excluded: Option<String>,
+    /// Whether it is neither a regular file nor a folder, e.g. a symlink.
+    #[serde(default, skip_serializing_if = "is_false")]
+    notreg: bool,
+    // only with extended information
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Metadata {
+    #[serde(skip_deserializing)]
+    progname: &'static str,
+    #[serde(skip_deserializing)]
+    progver: &'static str,
+    timestamp: Option<u64>,
+}
+
+/// A folder is exported as an array of its own information followed by its entries, anything else as an object.
+#[derive(Debug)]
+enum Node {
+    Folder(Info, Vec<Node>),
+    Other(Info),
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NodeVisitor;
+
+        impl<'de> Visitor<'de> for NodeVisitor {
+            type Value = Node;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an ncdu entry or folder")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Node, A::Error> {
+                Ok(Node::Other(Info::deserialize(MapAccessDeserializer::new(map))?))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+                let info = seq.next_element()?.ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let mut entries = vec![];
+                while let Some(entry) = seq.next_element()? {
+                    entries.push(entry);
+                }
+                Ok(Node::Folder(info, entries))
+            }
+        }
+
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+/// Reads a tree exported by `ncdu -o`, taken when its export says, or when it was `modified` for exports that don't.
+/// Excluded entries, including other file systems, are shown as excluded and symlinks as links, without targets as
+/// ncdu doesn't keep those. Of the hard links to a file only the first is counted, as when scanning.
+pub(crate) fn read_ncdu(reader: impl Read, modified: SystemTime) -> Result<Snapshot, Box<dyn Error>> {
+    let (major, _minor, metadata, root): (u64, u64, Metadata, Node) =
+        serde_json::from_reader(reader).map_err(|error| format!("not an ncdu export: {}", error))?;
+    if major != MAJOR_VERSION {
+        return Err(format!("unsupported ncdu export version {}", major).into());
+    }
+    let taken = metadata.timestamp.map_or(modified, unix_time);
+    let root = match root {
+        Node::Folder(info, entries) => {
+            let path = PathBuf::from(&info.name);
+            ncdu_folder(info, path, entries, None, &mut HashSet::new(), true)
+        }
+        Node::Other(_) => return Err("an ncdu export of a file rather than a folder".into()),
+    };
+    Ok(Snapshot { taken, root })
+}
+
+/// Writes `snapshot` in the format `ncdu -o` exports, for ncdu to browse with `ncdu -f`. Rolled up files are written
+/// one by one, mount points as other file systems, and collapsed folders as if their totals were their own size.
+pub(crate) fn write_ncdu(mut writer: impl Write, snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+    let timestamp = unix_seconds(snapshot.taken);
+    let metadata = Metadata { progname: env!("CARGO_PKG_NAME"), progver: env!("CARGO_PKG_VERSION"), timestamp };
+    write!(writer, "[{},{},", MAJOR_VERSION, MINOR_VERSION)?;
+    serde_json::to_writer(&mut writer, &metadata)?;
+    writer.write_all(b",\n")?;
+    write_entry(&mut writer, &snapshot.root)?;
+    writer.write_all(b"]\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_entry(writer: &mut impl Write, entry: &DirectoryEntry) -> Result<(), Box<dyn Error>> {
+    let name = match entry.is_root() {
+        true => entry.path().to_string_lossy().to_string(),
+        false => entry.path().file_name().map_or(String::new(), |name| name.to_string_lossy().to_string()),
+    };
+    let info = Info { name, ..Info::default() };
+    match entry {
+        DirectoryEntry::File { len, disk_len, hard_links, times, owner, .. } => {
+            let info = Info {
+                asize: len.0,
+                dsize: disk_len.0,
+                dev: hard_links.map(|hard_links| hard_links.id.device),
+                ino: hard_links.map(|hard_links| hard_links.id.inode),
+                hlnkc: hard_links.is_some(),
+                nlink: hard_links.map(|hard_links| hard_links.count),
+                uid: owner.map(|owner| owner.uid),
+                gid: owner.map(|owner| owner.gid),
+                mtime: times.modified.and_then(unix_seconds),
+                ..info
+            };
+            serde_json::to_writer(writer, &info)?;
+        }
+        DirectoryEntry::Folder { len, disk_len, is_collapsed: true, .. } => {
+            writer.write_all(b"[")?;
+            serde_json::to_writer(&mut *writer, &Info { asize: len.0, dsize: disk_len.0, ..info })?;
+            writer.write_all(b"]")?;
+        }
+        DirectoryEntry::Folder { .. } => {
+            writer.write_all(b"[")?;
+            serde_json::to_writer(&mut *writer, &info)?;
+            for child in entry.unrolled_entries() {
+                writer.write_all(b",\n")?;
+                write_entry(writer, child)?;
+            }
+            writer.write_all(b"]")?;
+        }
+        DirectoryEntry::Link { .. } => serde_json::to_writer(writer, &Info { notreg: true, ..info })?,
+        DirectoryEntry::Excluded { rule, .. } => {
+            // rules of ncdu's own, from an import, are kept
+            let excluded = rule.strip_prefix("ncdu ").unwrap_or("pattern").to_string();
+            serde_json::to_writer(writer, &Info { excluded: Some(excluded), ..info })?
+        }
+        DirectoryEntry::MountPoint { .. } => {
+            serde_json::to_writer(writer, &Info { excluded: Some("otherfs".to_string()), ..info })?
+        }
+        DirectoryEntry::Unreadable { is_dir: true, .. } => {
+            writer.write_all(b"[")?;
+            serde_json::to_writer(&mut *writer, &Info { read_error: true, ..info })?;
+            writer.write_all(b"]")?;
+        }
+        DirectoryEntry::Unreadable { .. } => serde_json::to_writer(writer, &Info { read_error: true, ..info })?,
+        // only ever below a folder
+        DirectoryEntry::Rollup { .. } => {}
+    }
+    Ok(())
+}
+
+/// `dev` is the device of the folder containing it, which it is on unless it says otherwise, and `seen_links` the
+/// files with hard links that have already been counted.
+fn ncdu_entry(node: Node, folder: &Path, dev: Option<u64>, seen_links: &mut HashSet<FileId>) -> DirectoryEntry {
+    match node {
+        Node::Folder(info, entries) => {
+            let path = folder.join(&info.name);
+            ncdu_folder(info, path, entries, dev, seen_links, false)
+        }
+        Node::Other(info) => {
+            let path = folder.join(&info.name);
+            let is_hidden = is_hidden(&info);
+            if let Some(excluded) = info.excluded {
+                // other file systems are mounted on folders, and patterns can exclude either
+                let is_dir = excluded != "pattern";
+                DirectoryEntry::new_excluded(path, is_dir, is_hidden, false, format!("ncdu {}", excluded))
+            } else if info.read_error {
+                DirectoryEntry::new_unreadable(path, &read_error(), false, is_hidden)
+            } else if info.notreg && info.mode.is_none_or(|mode| mode & S_IFMT == S_IFLNK) {
+                DirectoryEntry::new_link(path, None, false, is_hidden)
+            } else {
+                let (len, disk_len) = (Byteable(info.asize), Byteable(info.dsize));
+                let times = Timestamps { modified: info.mtime.map(unix_time), ..Timestamps::default() };
+                let owner = info.uid.zip(info.gid).map(|(uid, gid)| Owner { uid, gid });
+                match info.ino.filter(|_| info.hlnkc) {
+                    Some(ino) => {
+                        let id = FileId { device: info.dev.or(dev).unwrap_or_default(), inode: ino };
+                        let hard_links =
+                            HardLinks { count: info.nlink.unwrap_or(2), is_counted: seen_links.insert(id), id };
+                        DirectoryEntry::new_hard_link(len, disk_len, path, is_hidden, hard_links, times, owner)
+                    }
+                    None => DirectoryEntry::new_file(len, disk_len, path, is_hidden, times, owner),
+                }
+            }
+        }
+    }
+}
+
+/// A folder that couldn't be read has whatever ncdu could read of it, and is unreadable if that's nothing.
+fn ncdu_folder(
+    info: Info, path: PathBuf, entries: Vec<Node>, dev: Option<u64>, seen_links: &mut HashSet<FileId>, is_root: bool,
+) -> DirectoryEntry {
+    let is_hidden = is_hidden(&info);
+    if info.read_error && entries.is_empty() && !is_root {
+        return DirectoryEntry::new_unreadable(path, &read_error(), true, is_hidden);
+    }
+    let dev = info.dev.or(dev);
+    let entries: Vec<DirectoryEntry> =
+        entries.into_iter().map(|node| ncdu_entry(node, &path, dev, seen_links)).collect();
+    let sum = |measure| entries.iter().map(|entry| entry.size(measure).map_or(0, |size| size.0)).sum();
+    let (len, disk_len) = (Byteable(sum(SizeMeasure::ApparentSize)), Byteable(sum(SizeMeasure::DiskUsage)));
+    let mut folder = DirectoryEntry::new_folder(len, disk_len, path, is_hidden, entries, is_root);
+    if let DirectoryEntry::Folder { errors, .. } = &mut folder {
+        *errors += info.read_error as u64;
+    }
+    folder
+}
+
+/// ncdu only says that there was an error, not what it was.
+fn read_error() -> io::Error { io::Error::other("ncdu couldn't read it") }
+
+fn is_zero(value: &u64) -> bool { *value == 0 }
+
+fn is_false(value: &bool) -> bool { !value }
+
+fn is_hidden(info: &Info) -> bool { info.name.starts_with('.') }
+
+fn unix_time(seconds: u64) -> SystemTime { UNIX_EPOCH + Duration::from_secs(seconds) }
+
+fn unix_seconds(time: SystemTime) -> Option<u64> { time.duration_since(UNIX_EPOCH).ok().map(|since| since.as_secs()) }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::file_analysis::file_system_proxy_traits::FileId;
+    use crate::file_analysis::file_types::{Byteable, DirectoryEntry, HardLinks, SizeMeasure};
+    use crate::file_analysis::mock_utils::MockEntry;
+    use crate::file_analysis::ncdu::{read_ncdu, write_ncdu};
+    use crate::file_analysis::progress::ScanProgress;
+    use crate::file_analysis::snapshot::Snapshot;
+    use crate::file_analysis::tests::read_tree;
+    use crate::file_analysis::{mock_utils, ScanOptions};
+
+    const EXPORT: &str = r#"[1, 2, {"progname": "ncdu", "progver": "1.19", "timestamp": 1700000000},
+        [{"name": "/srv", "asize": 4096, "dsize": 4096, "dev": 2049, "ino": 2},
+            {"name": "big", "asize": 1000, "dsize": 4096, "ino": 10, "mtime": 1600000000, "uid": 1000, "gid": 100},
+            {"name": "linked", "asize": 300, "dsize": 4096, "ino": 11, "hlnkc": true, "nlink": 2},
+            [{"name": ".cache", "asize": 4096, "dsize": 4096, "ino": 12},
+                {"name": "again", "asize": 300, "dsize": 4096, "ino": 11, "hlnkc": true, "nlink": 2},
+                {"name": "current", "asize": 7, "notreg": true}],
+            {"name": "backup", "excluded": "otherfs"},
+            {"name": "tmp.log", "excluded": "pattern"},
+            {"name": "secret", "read_error": true},
+            [{"name": "partial", "read_error": true},
+                {"name": "found", "asize": 50, "dsize": 4096}]
+        ]]"#;
+
+    #[test]
+    fn test_read_ncdu() {
+        let snapshot = read_ncdu(EXPORT.as_bytes(), UNIX_EPOCH).expect("an export");
+        let root = snapshot.root;
+
+        assert_eq!(UNIX_EPOCH + Duration::from_secs(1_700_000_000), snapshot.taken);
+        assert_eq!(Path::new("/srv"), root.path());
+        assert!(root.is_root());
+        assert_eq!(Some(Byteable(1350)), root.size(SizeMeasure::ApparentSize));
+        assert_eq!(2, root.errors());
+        let find = |path: &str| root.find(Path::new(path)).expect("an entry");
+        let big = find("/srv/big");
+        assert_eq!(Some(Byteable(4096)), big.size(SizeMeasure::DiskUsage));
+        assert_eq!(Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)), big.times().modified);
+        let id = FileId { device: 2049, inode: 11 };
+        assert_eq!(Some(HardLinks { count: 2, is_counted: true, id }), find("/srv/linked").hard_links());
+        assert_eq!(Some(HardLinks { count: 2, is_counted: false, id }), find("/srv/.cache/again").hard_links());
+        assert!(find("/srv/.cache").is_hidden());
+        assert!(matches!(find("/srv/.cache/current"), DirectoryEntry::Link { target: None, .. }));
+        assert!(matches!(
+            find("/srv/backup"),
+            DirectoryEntry::Excluded { is_dir: true, rule, .. } if rule == "ncdu otherfs"
+        ));
+        assert!(matches!(find("/srv/tmp.log"), DirectoryEntry::Excluded { is_dir: false, .. }));
+        assert!(matches!(find("/srv/secret"), DirectoryEntry::Unreadable { is_dir: false, .. }));
+        let partial = find("/srv/partial");
+        assert!(partial.is_dir());
+        assert_eq!((Some(Byteable(50)), 1), (partial.size(SizeMeasure::ApparentSize), partial.errors()));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(vec![
+            MockEntry::Modified("a", 10, 1_000_000),
+            MockEntry::Owned("b", 20, 1000, 100),
+            MockEntry::HardLink("x", 100, 500),
+            MockEntry::Folder("c", vec![MockEntry::File("d.txt", 30), MockEntry::HardLink("y", 100, 500)]),
+            MockEntry::Symlink("e", "a"),
+            MockEntry::Mount("g", "nfs", vec![MockEntry::File("h", 40)]),
+        ]);
+        let options = ScanOptions { threads: 1, one_file_system: true, ..ScanOptions::default() };
+        let root = read_tree(dir.clone(), &mock_file_operations, options, &ScanProgress::default());
+        let snapshot = Snapshot { taken: UNIX_EPOCH + Duration::from_secs(1_700_000_000), root };
+
+        let mut file = vec![];
+        write_ncdu(&mut file, &snapshot).expect("written");
+        assert!(file.starts_with(b"[1,2,{\"progname\":\"fodlereyez\""));
+        let exported = read_ncdu(file.as_slice(), UNIX_EPOCH).expect("an export");
+
+        assert_eq!(snapshot.taken, exported.taken);
+        let (scanned, root) = (&snapshot.root, &exported.root);
+        for measure in [SizeMeasure::ApparentSize, SizeMeasure::DiskUsage, SizeMeasure::EntryCount] {
+            assert_eq!(scanned.size(measure), root.size(measure));
+        }
+        assert_eq!(scanned.owners(), root.owners());
+        fn find<'a>(root: &'a DirectoryEntry, path: &str) -> &'a DirectoryEntry {
+            root.find(&root.path().join(path)).expect("an entry")
+        }
+        assert_eq!(Some(UNIX_EPOCH + Duration::from_secs(1_000_000)), find(root, "a").times().modified);
+        for path in ["x", "c/y"] {
+            assert_eq!(find(scanned, path).hard_links(), find(root, path).hard_links());
+        }
+        assert!(matches!(find(root, "e"), DirectoryEntry::Link { .. }));
+        assert!(
+            matches!(find(root, "g"), DirectoryEntry::Excluded { is_dir: true, rule, .. } if rule == "ncdu otherfs")
+        );
+    }
+
+    #[test]
+    fn test_read_other_file() {
+        let error = read_ncdu(b"[2, 0, {}, [{\"name\": \"/\"}]]".as_slice(), UNIX_EPOCH).expect_err("a newer version");
+        assert_eq!("unsupported ncdu export version 2", error.to_string());
+        assert!(read_ncdu(b"FDZ\x01".as_slice(), UNIX_EPOCH).is_err());
+        let taken = read_ncdu(b"[1, 0, {}, [{\"name\": \"/\"}]]".as_slice(), UNIX_EPOCH).expect("an export").taken;
+        assert_eq!(UNIX_EPOCH, taken);
+    }
+}
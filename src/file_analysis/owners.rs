@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_analysis::file_types::FileTotal;
+
+/// The ids of the user and group that own a file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct Owner {
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+}
+
+/// The totals of the files below a folder by the user and by the group that owns them.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct OwnerTotals {
+    pub(crate) users: BTreeMap<u32, FileTotal>,
+    pub(crate) groups: BTreeMap<u32, FileTotal>,
+}
+
+impl OwnerTotals {
+    pub(crate) fn add_file(&mut self, owner: Owner, total: &FileTotal) {
+        self.users.entry(owner.uid).or_default().add(total);
+        self.groups.entry(owner.gid).or_default().add(total);
+    }
+    pub(crate) fn add(&mut self, other: &OwnerTotals) {
+        for (uid, total) in other.users.iter() {
+            self.users.entry(*uid).or_default().add(total);
+        }
+        for (gid, total) in other.groups.iter() {
+            self.groups.entry(*gid).or_default().add(total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_analysis::file_types::FileTotal;
+    use crate::file_analysis::owners::{Owner, OwnerTotals};
+
+    #[test]
+    fn test_add_totals() {
+        let mut totals = OwnerTotals::default();
+        totals.add_file(Owner { uid: 1000, gid: 100 }, &FileTotal { len: 10, disk_len: 4096, files: 1 });
+        let mut other = OwnerTotals::default();
+        other.add_file(Owner { uid: 1000, gid: 200 }, &FileTotal { len: 5, disk_len: 4096, files: 1 });
+        other.add_file(Owner { uid: 0, gid: 100 }, &FileTotal { len: 1, disk_len: 0, files: 1 });
+        totals.add(&other);
+
+        assert_eq!(Some(&FileTotal { len: 15, disk_len: 8192, files: 2 }), totals.users.get(&1000));
+        assert_eq!(Some(&FileTotal { len: 1, disk_len: 0, files: 1 }), totals.users.get(&0));
+        assert_eq!(Some(&FileTotal { len: 11, disk_len: 4096, files: 2 }), totals.groups.get(&100));
+        assert_eq!(Some(&FileTotal { len: 5, disk_len: 4096, files: 1 }), totals.groups.get(&200));
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::file_analysis::file_types::Byteable;
+
+/// Shared between the scanning threads and whatever is watching the scan, which polls it with `report` and can ask
+/// for the scan to stop with `cancel`.
+#[derive(Default)]
+pub(crate) struct ScanProgress {
+    current_path: Mutex<PathBuf>,
+    entries: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProgressReport {
+    pub(crate) current_path: PathBuf,
+    pub(crate) entries: u64,
+    pub(crate) bytes: Byteable,
+    pub(crate) errors: u64,
+}
+
+impl ScanProgress {
+    pub(crate) fn report(&self) -> ProgressReport {
+        ProgressReport {
+            current_path: self.current_path.lock().map(|path| path.clone()).unwrap_or_default(),
+            entries: self.entries.load(Ordering::Relaxed),
+            bytes: Byteable(self.bytes.load(Ordering::Relaxed)),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Starts counting from nothing again for another scan. Cancelling stays cancelled.
+    pub(crate) fn reset(&self) {
+        if let Ok(mut current_path) = self.current_path.lock() {
+            *current_path = PathBuf::new();
+        }
+        self.entries.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cancel(&self) { self.cancelled.store(true, Ordering::Relaxed) }
+
+    pub(crate) fn is_cancelled(&self) -> bool { self.cancelled.load(Ordering::Relaxed) }
+
+    pub(super) fn enter_directory(&self, path: &Path) {
+        // only informational, so don't hold up a scanning thread if another one is already updating it
+        if let Ok(mut current_path) = self.current_path.try_lock() {
+            *current_path = path.to_path_buf();
+        }
+    }
+
+    pub(super) fn add_entry(&self, bytes: u64) {
+        self.entries.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_error(&self) { self.errors.fetch_add(1, Ordering::Relaxed); }
+}
@@ -0,0 +1,135 @@
+use std::error::Error;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::file_analysis::file_types::DirectoryEntry;
+
+/// What snapshot files start with, followed by the version of their format.
+const MAGIC: &[u8; 3] = b"FDZ";
+/// Version 2 keeps which hard links are to the same file, version 3 only the owners of collapsed folders, and
+/// version 4 only their extensions too.
+const VERSION: u8 = 4;
+
+/// A scanned tree, as it was when it was taken.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    pub(crate) taken: SystemTime,
+    pub(crate) root: DirectoryEntry,
+}
+
+/// Writes `snapshot` to `writer` as a gzipped binary encoding of the tree.
+pub(crate) fn write_snapshot(mut writer: impl Write, snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    bincode::serialize_into(&mut encoder, snapshot)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Reads a snapshot written by `write_snapshot`, or fails if that isn't what `reader` has.
+pub(crate) fn read_snapshot(mut reader: impl Read) -> Result<Snapshot, Box<dyn Error>> {
+    let mut header = [0; 4];
+    reader.read_exact(&mut header).map_err(|_| "not a snapshot file")?;
+    if &header[..3] != MAGIC {
+        return Err("not a snapshot file".into());
+    }
+    if header[3] != VERSION {
+        return Err(format!("unsupported snapshot version {}", header[3]).into());
+    }
+    Ok(bincode::deserialize_from(GzDecoder::new(reader))?)
+}
+
+/// Paths are kept as bytes on unix, where they needn't be UTF-8, rather than failing to save them.
+pub(crate) mod path_bytes {
+    #[cfg(unix)]
+    use std::ffi::OsString;
+    #[cfg(unix)]
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[cfg(unix)]
+    pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        path.as_os_str().as_bytes().serialize(serializer)
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(PathBuf::from(OsString::from_vec(Vec::deserialize(deserializer)?)))
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        path.to_string_lossy().serialize(serializer)
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(PathBuf::from(String::deserialize(deserializer)?))
+    }
+}
+
+/// As `path_bytes`, for paths that may be missing.
+pub(crate) mod optional_path_bytes {
+    use std::path::PathBuf;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::path_bytes;
+
+    /// Lets `path_bytes` be used inside an `Option`.
+    #[derive(Serialize, Deserialize)]
+    struct PathBytes(#[serde(with = "path_bytes")] PathBuf);
+
+    pub(crate) fn serialize<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+        path.clone().map(PathBytes).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<PathBytes>::deserialize(deserializer)?.map(|PathBytes(path)| path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::file_analysis::mock_utils::MockEntry;
+    use crate::file_analysis::progress::ScanProgress;
+    use crate::file_analysis::snapshot::{read_snapshot, write_snapshot, Snapshot};
+    use crate::file_analysis::tests::read_tree;
+    use crate::file_analysis::{mock_utils, ScanOptions};
+
+    #[test]
+    fn test_round_trip() {
+        let (dir, mock_file_operations) = mock_utils::set_expect_tree(vec![
+            MockEntry::Modified("a", 10, 1_000_000),
+            MockEntry::Owned("b", 20, 1000, 100),
+            MockEntry::Folder("c", vec![MockEntry::File("d.txt", 30), MockEntry::Symlink("e", "a")]),
+            MockEntry::Unreadable("f"),
+        ]);
+        let options = ScanOptions { threads: 1, ..ScanOptions::default() };
+        let root = read_tree(dir, &mock_file_operations, options, &ScanProgress::default());
+        let snapshot = Snapshot { taken: UNIX_EPOCH + Duration::from_secs(1_700_000_000), root };
+
+        let mut file = vec![];
+        write_snapshot(&mut file, &snapshot).expect("written");
+        assert_eq!(b"FDZ\x04", &file[..4]);
+
+        assert_eq!(snapshot, read_snapshot(file.as_slice()).expect("read"));
+    }
+
+    #[test]
+    fn test_read_other_file() {
+        let error = read_snapshot(b"{\"not\": \"a snapshot\"}".as_slice()).expect_err("not a snapshot");
+        assert_eq!("not a snapshot file", error.to_string());
+        assert!(read_snapshot(b"FD".as_slice()).is_err());
+    }
+}
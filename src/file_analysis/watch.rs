@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+#[cfg(test)]
+use mockall::automock;
+
+use crate::file_analysis::file_system_proxy_traits::{FileId, FileSystemProxy};
+use crate::file_analysis::file_types::{DirectoryEntry, PathLinks};
+use crate::file_analysis::progress::ScanProgress;
+use crate::file_analysis::{read_entry, ScanOptions};
+
+/// How long to wait between polling for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A change to an entry of a watched folder.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum WatchEvent {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Deleted(PathBuf),
+}
+
+impl WatchEvent {
+    fn path(&self) -> &Path {
+        match self {
+            WatchEvent::Created(path) | WatchEvent::Modified(path) | WatchEvent::Deleted(path) => path,
+        }
+    }
+}
+
+/// Reports changes to the entries of folders, but not of the folders below them. Must be `Send` as it is polled
+/// from its own thread.
+#[cfg_attr(test, automock)]
+pub(crate) trait WatcherProxy: Send {
+    fn watch(&mut self, folder: &Path) -> Result<(), Box<dyn Error>>;
+    fn unwatch(&mut self, folder: &Path);
+    /// The changes since it was last polled, without waiting for any.
+    fn poll_events(&mut self) -> Vec<WatchEvent>;
+}
+
+/// How the files with more than one hard link are counted in the scanned tree with respect to a path, as
+/// `DirectoryEntry::links_of` finds them.
+pub(crate) type TreeLinks = dyn Fn(&Path) -> PathLinks;
+
+/// How the scanned tree has to change to match the file system.
+#[derive(Debug)]
+pub(crate) enum TreeChange {
+    /// To be put in place of any entry with the same path, with `DirectoryEntry::insert`.
+    Changed(Box<DirectoryEntry>),
+    Removed(PathBuf),
+}
+
+/// Reads the entries that `events` have changed. Only the last event for each path counts, and none for a path
+/// below a folder that is read again anyway. Created or modified entries that have gone since are removed. Hard links
+/// are only counted if they aren't counted elsewhere in the tree, or in what has been read before them. If a removed
+/// entry held counted links, the other links to those files are read again so that one of them is counted instead.
+pub(crate) fn read_changes(
+    events: Vec<WatchEvent>, file_operations: &impl FileSystemProxy, options: &ScanOptions, tree_links: &TreeLinks,
+    progress: &ScanProgress,
+) -> Vec<TreeChange> {
+    let mut is_deleted: BTreeMap<PathBuf, bool> = BTreeMap::new();
+    for event in events {
+        let deleted = matches!(event, WatchEvent::Deleted(_));
+        is_deleted.insert(event.path().to_path_buf(), deleted);
+    }
+    let is_read_again = |path: &Path| path.ancestors().skip(1).any(|folder| is_deleted.get(folder) == Some(&false));
+    let (mut removed, mut to_read) = (vec![], VecDeque::new());
+    for (path, &deleted) in is_deleted.iter().filter(|&(path, _)| !is_read_again(path)) {
+        match deleted {
+            true => removed.push(path.clone()),
+            false => to_read.push_back(path.clone()),
+        }
+    }
+    let mut changes = vec![];
+    let mut removed_links: HashSet<FileId> = HashSet::new();
+    let mut read_links: HashSet<FileId> = HashSet::new();
+    loop {
+        // removals first, so that nothing is read while a link removed with them still looks counted
+        for path in removed.drain(..) {
+            let links = tree_links(&path);
+            removed_links.extend(links.counted_below);
+            // those removed or read anyway aren't read again
+            let is_changed = |link: &Path| link.ancestors().any(|path| is_deleted.contains_key(path));
+            for link in links.other_links {
+                if !is_changed(&link) && !to_read.contains(&link) {
+                    to_read.push_back(link);
+                }
+            }
+            changes.push(TreeChange::Removed(path));
+        }
+        let Some(path) = to_read.pop_front() else {
+            return changes;
+        };
+        let counted_links = tree_links(&path).counted_elsewhere;
+        let counted_links = counted_links.difference(&removed_links).chain(&read_links).copied().collect();
+        match read_entry(path.clone(), file_operations, options, counted_links, progress) {
+            Some(entry) => {
+                read_links.extend(entry.counted_links(None));
+                changes.push(TreeChange::Changed(Box::new(entry)));
+            }
+            None => removed.push(path),
+        }
+    }
+}
+
+/// Watches the folders last sent through `requests` with `watcher`, passing `on_changes` what has changed in them
+/// each time it is polled, until `requests` is disconnected. Folders that are created or removed are watched or
+/// forgotten along with them. Hard links are counted as `read_changes` does.
+pub(crate) fn watch_folders(
+    mut watcher: impl WatcherProxy, requests: Receiver<Vec<PathBuf>>, file_operations: &impl FileSystemProxy,
+    options: &ScanOptions, tree_links: &TreeLinks, progress: &ScanProgress, on_changes: &dyn Fn(Vec<TreeChange>),
+) {
+    let mut watched: HashSet<PathBuf> = HashSet::new();
+    loop {
+        match requests.recv_timeout(POLL_INTERVAL) {
+            Ok(folders) => {
+                let folders: HashSet<PathBuf> = folders.into_iter().collect();
+                for folder in watched.difference(&folders) {
+                    watcher.unwatch(folder);
+                }
+                // a folder that can't be watched, e.g. as it has gone, just isn't
+                watched = folders
+                    .into_iter()
+                    .filter(|folder| watched.contains(folder) || watcher.watch(folder).is_ok())
+                    .collect();
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        let events = watcher.poll_events();
+        if events.is_empty() {
+            continue;
+        }
+        let changes = read_changes(events, file_operations, options, tree_links, progress);
+        for change in changes.iter() {
+            match change {
+                TreeChange::Changed(entry) => {
+                    for folder in entry.folders() {
+                        if !watched.contains(folder) && watcher.watch(folder).is_ok() {
+                            watched.insert(folder.to_path_buf());
+                        }
+                    }
+                }
+                TreeChange::Removed(path) => watched.retain(|folder| {
+                    let is_removed = folder.starts_with(path);
+                    if is_removed {
+                        watcher.unwatch(folder);
+                    }
+                    !is_removed
+                }),
+            }
+        }
+        on_changes(changes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::{mpsc, Mutex};
+
+    use crate::file_analysis::file_types::{PathLinks, SizeMeasure};
+    use crate::file_analysis::mock_utils::MockEntry;
+    use crate::file_analysis::progress::ScanProgress;
+    use crate::file_analysis::tests::read_tree;
+    use crate::file_analysis::watch::{watch_folders, MockWatcherProxy, TreeChange, WatchEvent};
+    use crate::file_analysis::{mock_utils, ScanOptions};
+
+    #[test]
+    fn test_changes_match_rescan() {
+        let options = || ScanOptions { threads: 1, ..ScanOptions::default() };
+        let progress = ScanProgress::default();
+        let (dir, before) = mock_utils::set_expect_tree(vec![
+            MockEntry::File("a", 10),
+            MockEntry::File("b", 20),
+            MockEntry::Folder("c", vec![MockEntry::File("d", 30), MockEntry::File("e", 40)]),
+            MockEntry::Folder("f", vec![MockEntry::File("g", 50)]),
+        ]);
+        let mut tree = read_tree(dir.clone(), &before, options(), &progress);
+        let (_, after) = mock_utils::set_expect_tree(vec![
+            MockEntry::File("a", 15),
+            MockEntry::Folder("c", vec![MockEntry::File("d", 30), MockEntry::File("h", 60)]),
+            MockEntry::Folder("f", vec![MockEntry::File("g", 50)]),
+            MockEntry::Folder("i", vec![MockEntry::File("j", 70)]),
+        ]);
+
+        let mut watcher = MockWatcherProxy::new();
+        watcher.expect_watch().returning(|_| Ok(()));
+        // "f" once no longer requested, and "i" once found but not requested either
+        watcher.expect_unwatch().times(2).return_const(());
+        let mut events = vec![
+            WatchEvent::Modified(dir.join("a")),
+            WatchEvent::Deleted(dir.join("b")),
+            WatchEvent::Created(dir.join("c/h")),
+            WatchEvent::Deleted(dir.join("c/e")),
+            // read with the new folder
+            WatchEvent::Created(dir.join("i")),
+            WatchEvent::Created(dir.join("i/j")),
+        ];
+        watcher.expect_poll_events().returning(move || std::mem::take(&mut events));
+        let (requests, received) = mpsc::channel();
+        let folders: Vec<PathBuf> = tree.folders().into_iter().map(PathBuf::from).collect();
+        requests.send(folders).expect("send");
+        // no longer watched
+        requests.send(vec![dir.clone(), dir.join("c")]).expect("send");
+        drop(requests);
+        let changes = Mutex::new(vec![]);
+
+        watch_folders(watcher, received, &after, &options(), &|_| PathLinks::default(), &progress, &|batch| {
+            changes.lock().expect("lock").extend(batch)
+        });
+
+        let changes = changes.into_inner().expect("lock");
+        assert_eq!(5, changes.len());
+        for change in changes {
+            match change {
+                TreeChange::Changed(entry) => assert!(tree.insert(*entry, SizeMeasure::ApparentSize)),
+                TreeChange::Removed(path) => assert!(tree.remove(&path, SizeMeasure::ApparentSize)),
+            }
+        }
+        assert_eq!(read_tree(dir, &after, options(), &progress), tree);
+    }
+
+    #[test]
+    fn test_hard_link_changes_match_rescan() {
+        let options = || ScanOptions { threads: 1, ..ScanOptions::default() };
+        let progress = ScanProgress::default();
+        let (dir, before) = mock_utils::set_expect_tree(vec![
+            MockEntry::HardLink("a", 10, 7),
+            MockEntry::Folder("b", vec![MockEntry::HardLink("c", 10, 7), MockEntry::File("d", 20)]),
+        ]);
+        let mut tree = read_tree(dir.clone(), &before, options(), &progress);
+        let (_, after) = mock_utils::set_expect_tree(vec![
+            MockEntry::HardLink("a", 10, 7),
+            MockEntry::Folder(
+                "b",
+                vec![MockEntry::HardLink("c", 10, 7), MockEntry::File("d", 20), MockEntry::HardLink("e", 30, 8)],
+            ),
+            MockEntry::HardLink("f", 30, 8),
+        ]);
+
+        let mut watcher = MockWatcherProxy::new();
+        watcher.expect_watch().returning(|_| Ok(()));
+        // both links to the new file, and one to the old file, whose other link is outside the folder
+        let mut events = vec![
+            WatchEvent::Modified(dir.join("b/c")),
+            WatchEvent::Created(dir.join("b/e")),
+            WatchEvent::Created(dir.join("f")),
+        ];
+        watcher.expect_poll_events().returning(move || std::mem::take(&mut events));
+        let (requests, received) = mpsc::channel();
+        requests.send(vec![dir.clone(), dir.join("b")]).expect("send");
+        drop(requests);
+        let changes = Mutex::new(vec![]);
+
+        // as the tree was before the changes
+        let scanned = read_tree(dir.clone(), &before, options(), &progress);
+        let tree_links = move |path: &Path| scanned.links_of(path);
+        watch_folders(watcher, received, &after, &options(), &tree_links, &progress, &|batch| {
+            changes.lock().expect("lock").extend(batch)
+        });
+
+        for change in changes.into_inner().expect("lock") {
+            match change {
+                TreeChange::Changed(entry) => assert!(tree.insert(*entry, SizeMeasure::ApparentSize)),
+                TreeChange::Removed(path) => assert!(tree.remove(&path, SizeMeasure::ApparentSize)),
+            }
+        }
+        assert_eq!(60, tree.len().expect("a len").0);
+        assert_eq!(read_tree(dir, &after, options(), &progress), tree);
+    }
+
+    #[test]
+    fn test_removed_counted_link_counts_another() {
+        let options = || ScanOptions { threads: 1, ..ScanOptions::default() };
+        let progress = ScanProgress::default();
+        let (dir, before) = mock_utils::set_expect_tree(vec![
+            MockEntry::HardLink("a", 10, 7),
+            MockEntry::Folder("b", vec![MockEntry::HardLink("c", 10, 7), MockEntry::File("d", 20)]),
+            MockEntry::HardLink("e", 10, 7),
+        ]);
+        let mut tree = read_tree(dir.clone(), &before, options(), &progress);
+        let counted = tree.find(&dir.join("a")).and_then(|entry| entry.hard_links()).expect("a link");
+        assert!(counted.is_counted);
+        let (_, after) = mock_utils::set_expect_tree(vec![
+            MockEntry::Folder("b", vec![MockEntry::HardLink("c", 10, 7), MockEntry::File("d", 20)]),
+            MockEntry::HardLink("e", 10, 7),
+        ]);
+
+        let mut watcher = MockWatcherProxy::new();
+        watcher.expect_watch().returning(|_| Ok(()));
+        let mut events = vec![WatchEvent::Deleted(dir.join("a"))];
+        watcher.expect_poll_events().returning(move || std::mem::take(&mut events));
+        let (requests, received) = mpsc::channel();
+        requests.send(vec![dir.clone(), dir.join("b")]).expect("send");
+        drop(requests);
+        let changes = Mutex::new(vec![]);
+
+        let scanned = read_tree(dir.clone(), &before, options(), &progress);
+        let tree_links = move |path: &Path| scanned.links_of(path);
+        watch_folders(watcher, received, &after, &options(), &tree_links, &progress, &|batch| {
+            changes.lock().expect("lock").extend(batch)
+        });
+
+        for change in changes.into_inner().expect("lock") {
+            match change {
+                TreeChange::Changed(entry) => assert!(tree.insert(*entry, SizeMeasure::ApparentSize)),
+                TreeChange::Removed(path) => assert!(tree.remove(&path, SizeMeasure::ApparentSize)),
+            }
+        }
+        assert_eq!(30, tree.len().expect("a len").0);
+        assert_eq!(read_tree(dir, &after, options(), &progress), tree);
+    }
+}
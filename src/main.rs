@@ -1,16 +1,42 @@
+use std::collections::HashSet;
 use std::env;
-use std::path::PathBuf;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 use clap::*;
 
-use crate::file_analysis::read_fs;
+use crate::file_analysis::diff::diff_trees;
+use crate::file_analysis::duplicates::{find_duplicates, format_report};
+use crate::file_analysis::exclusions::{default_config_path, read_config, Exclusions, DEFAULT_EXCLUDES};
+use crate::file_analysis::file_types::{Byteable, DirectoryEntry, SizeMeasure, TimeKind};
+use crate::file_analysis::ncdu::{read_ncdu, write_ncdu};
+use crate::file_analysis::progress::ScanProgress;
+use crate::file_analysis::snapshot::{read_snapshot, write_snapshot, Snapshot};
+#[cfg(target_os = "linux")]
+use crate::file_analysis::watch::watch_folders;
+use crate::file_analysis::{read_fs, ScanOptions};
 use crate::real_proxies::RealFileOperations;
-use crate::tui::display_result;
+#[cfg(target_os = "linux")]
+use crate::real_proxies::RealWatcher;
+use crate::report::{write_report, Report, ReportFormat};
+use crate::tui::{display_diff, display_result, Pane, Source, ViewOptions, Watch};
 
+mod color;
 mod file_analysis;
+mod patterns;
 mod real_proxies;
+mod report;
 mod tui;
 
+/// How many levels of folders are shown as soon as they are listed, rather than when they have been fully scanned.
+const LIVE_DEPTH: usize = 3;
+/// How long before something counts as stale, unless that is given.
+const DEFAULT_STALE_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
 #[derive(Parser)]
 #[command(about, long_about = None)]
 struct Args {
@@ -26,17 +52,293 @@ struct Args {
     /// Show hidden files and folders
     #[arg(short, long)]
     show_hidden: bool,
+    /// Number of threads used to scan the file tree. 0 uses one thread per logical CPU
+    #[arg(short, long, default_value_t = 0)]
+    threads: usize,
+    /// Measure sizes by the space allocated on disk rather than the amount of data in files
+    #[arg(short, long, conflicts_with = "apparent_size")]
+    disk_usage: bool,
+    /// Measure sizes by the amount of data in files. The default
+    #[arg(long)]
+    apparent_size: bool,
+    /// Sort and colour by how many entries there are rather than their size, to find what is using up inodes
+    #[arg(long, conflicts_with_all = ["disk_usage", "apparent_size"])]
+    count_mode: bool,
+    /// Show how many files and folders there are in each folder
+    #[arg(short = 'n', long)]
+    show_counts: bool,
+    /// Show what share each entry is of its folder, and of the whole tree
+    #[arg(long)]
+    show_percentages: bool,
+    /// Show a bar as long as the share each entry is of its folder
+    #[arg(long)]
+    show_graph: bool,
+    /// Only show what hasn't been modified for AGE, such as "1year", "6months" or "30days", sorted by how much
+    /// of each folder that is. Pressing "o" shows everything again, or only what hasn't been modified for a year
+    #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration)]
+    stale_since: Option<Duration>,
+    /// Don't scan folders on other file systems, such as /proc or mounted drives, unless they are entered. Not
+    /// supported on Windows
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+    /// Don't scan entries matching RULE, which is a glob, or a regex if it starts with "re:". Globs without a
+    /// separator match names, anything else the whole path. Can be repeated
+    #[arg(short, long, value_name = "RULE")]
+    exclude: Vec<String>,
+    /// Scan entries matching RULE even if they are excluded. Can be repeated
+    #[arg(short, long, value_name = "RULE")]
+    include: Vec<String>,
+    /// A file of rules, one "exclude RULE" or "include RULE" per line. Defaults to fodlereyez/config in the user's
+    /// config directory, if there is one
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Scan what symlinks link to, unless it is in the scanned tree already. Not supported on Windows
+    #[arg(short = 'L', long)]
+    follow_symlinks: bool,
+    /// Only keep the contents of folders up to N levels below the root. Deeper folders are shown with their total
+    /// size, and scanned again when opened
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Merge entries smaller than SIZE, such as 50MB, into their folder's "<other files...>", by disk usage with
+    /// --disk-usage
+    #[arg(long, value_name = "SIZE")]
+    min_size: Option<Byteable>,
+    /// Print the sets of files with the same contents, and how much space they waste, instead of showing the tree
+    #[arg(long)]
+    duplicates: bool,
+    /// Keep the tree up to date as files are created, modified and deleted, watching every folder, or only the
+    /// current one in large trees. Linux only
+    #[arg(short, long, conflicts_with = "duplicates")]
+    watch: bool,
+    /// Save the scanned tree to FILE, to be browsed later with --load, instead of showing it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["duplicates", "watch"])]
+    save: Option<PathBuf>,
+    /// Browse the tree saved to FILE with --save, without scanning anything
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["root_directory", "save", "duplicates", "watch"])]
+    load: Option<PathBuf>,
+    /// Browse what has changed between the trees saved to OLD and NEW with --save, sorted and coloured by how much
+    /// each entry has grown
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"], conflicts_with_all = ["root_directory", "load", "save", "duplicates", "watch"])]
+    diff: Option<Vec<PathBuf>>,
+    /// Browse the tree exported to FILE with `ncdu -o`, without scanning anything
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["root_directory", "load", "save", "duplicates", "watch", "diff"])]
+    import_ncdu: Option<PathBuf>,
+    /// Export the scanned tree to FILE in the format of `ncdu -o`, for ncdu or anything else that reads that, instead
+    /// of showing it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["duplicates", "watch", "save", "load", "diff", "import_ncdu"])]
+    export_ncdu: Option<PathBuf>,
+    /// Print a report of the scanned tree, or of the one loaded or imported, in FORMAT instead of showing it, so that
+    /// it can be used in scripts or without a terminal
+    #[arg(long, value_name = "FORMAT", conflicts_with_all = ["duplicates", "watch", "save", "diff", "export_ncdu"])]
+    report: Option<ReportFormat>,
+    /// How many levels below the root to report. Defaults to 3 for a tree, otherwise all of them
+    #[arg(long, value_name = "N", requires = "report")]
+    report_depth: Option<usize>,
+    /// How many of the largest entries of each folder to report. Defaults to 10 for a tree, otherwise all of them
+    #[arg(long, value_name = "K", requires = "report")]
+    report_top: Option<usize>,
 }
 
 fn main() {
-    let (valid_root_directory, page_size, hide_comments, show_hidden) = get_arguments();
-    println!("working on {}...", valid_root_directory.display());
-    let result = read_fs(valid_root_directory, &RealFileOperations);
-    display_result(result, page_size, hide_comments, show_hidden);
+    let (valid_root_directory, args) = get_arguments();
+    let progress = Arc::new(ScanProgress::default());
+    let scan_progress = progress.clone();
+    let measure = if args.count_mode {
+        SizeMeasure::EntryCount
+    } else if args.disk_usage {
+        SizeMeasure::DiskUsage
+    } else {
+        SizeMeasure::ApparentSize
+    };
+    let scan_options = Arc::new(ScanOptions {
+        threads: args.threads,
+        // reports need the whole tree, so there is no point sending it in parts
+        live_depth: if args.duplicates || args.save.is_some() || args.export_ncdu.is_some() || args.report.is_some() {
+            0
+        } else {
+            LIVE_DEPTH
+        },
+        one_file_system: args.one_file_system,
+        exclusions: get_exclusions(&args),
+        follow_symlinks: args.follow_symlinks,
+        max_depth: args.max_depth,
+        min_size: args.min_size.unwrap_or_default(),
+        measure,
+    });
+    if args.duplicates {
+        let root = scan_all(valid_root_directory, &scan_options, &progress);
+        print!("{}", format_report(&find_duplicates(root.duplicate_candidates(), &RealFileOperations, args.threads)));
+        return;
+    }
+    if let Some(path) = &args.save {
+        save_scan(path, valid_root_directory, &scan_options, &progress, write_snapshot);
+        return;
+    }
+    if let Some(path) = &args.export_ncdu {
+        save_scan(path, valid_root_directory, &scan_options, &progress, write_ncdu);
+        return;
+    }
+    let options = ViewOptions {
+        page_size: args.page_size,
+        hide_comments: args.hide_comments,
+        show_hidden: args.show_hidden,
+        measure,
+        show_counts: args.show_counts || args.count_mode,
+        show_percentages: args.show_percentages,
+        show_graph: args.show_graph,
+        time_column: args.stale_since.map(|_| TimeKind::Modified),
+        only_stale: args.stale_since.is_some(),
+        stale_age: args.stale_since.unwrap_or(DEFAULT_STALE_AGE),
+        pane: Pane::Entries,
+    };
+    if let Some([old, new]) = args.diff.as_deref() {
+        let (old, new) = (load_snapshot(old), load_snapshot(new));
+        display_diff(diff_trees(&old.root, &new.root), old.taken, options);
+        return;
+    }
+    if let Some(format) = args.report {
+        let mut root = match (&args.load, &args.import_ncdu) {
+            (Some(path), _) => load_snapshot(path).root,
+            (_, Some(path)) => import_ncdu(path).root,
+            (None, None) => scan_all(valid_root_directory, &scan_options, &progress),
+        };
+        if measure != SizeMeasure::ApparentSize {
+            root.sort_by(measure);
+        }
+        let report = Report {
+            format,
+            depth: args.report_depth,
+            top: args.report_top,
+            is_colored: io::stdout().is_terminal(),
+            measure,
+            show_hidden: args.show_hidden,
+            hide_comments: args.hide_comments,
+        };
+        match write_report(&mut io::stdout().lock(), &root, report) {
+            // e.g. piped to head
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(error) => {
+                Args::command().error(error::ErrorKind::Io, format!("can't write the report: {}", error)).exit()
+            }
+            Ok(()) => {}
+        }
+        return;
+    }
+    let source = match (&args.load, &args.import_ncdu) {
+        (Some(path), _) => Source::Snapshot(Box::new(load_snapshot(path))),
+        (_, Some(path)) => Source::Snapshot(Box::new(import_ncdu(path))),
+        (None, None) => {
+            let scan_options = scan_options.clone();
+            Source::Scan(
+                valid_root_directory,
+                Arc::new(move |path, is_root, counted_links, on_update| {
+                    read_fs(path, is_root, &RealFileOperations, &scan_options, counted_links, &scan_progress, on_update)
+                }),
+            )
+        }
+    };
+    let watch = args.watch.then(|| get_watch(scan_options.clone()));
+    let threads = args.threads;
+    let find_duplicates = Arc::new(move |files| find_duplicates(files, &RealFileOperations, threads));
+    display_result(source, find_duplicates, watch, progress, options);
+}
+
+/// Scans the whole tree below `root_directory` before returning it, where `options` has a `live_depth` of 0 so that
+/// it is sent as one update.
+fn scan_all(root_directory: PathBuf, options: &ScanOptions, progress: &ScanProgress) -> DirectoryEntry {
+    let root = Mutex::new(None);
+    read_fs(root_directory.clone(), true, &RealFileOperations, options, HashSet::new(), progress, &|tree| {
+        *root.lock().expect("lock") = Some(tree);
+    });
+    let root = root.into_inner().expect("lock");
+    root.unwrap_or_else(|| DirectoryEntry::new_folder(Byteable(0), Byteable(0), root_directory, false, vec![], true))
+}
+
+/// Scans the whole tree, as of when the scan started, and saves it to `path` with `write`.
+fn save_scan<W>(path: &Path, root_directory: PathBuf, options: &ScanOptions, progress: &ScanProgress, write: W)
+where
+    W: FnOnce(BufWriter<File>, &Snapshot) -> Result<(), Box<dyn Error>>,
+{
+    let taken = SystemTime::now();
+    let root = scan_all(root_directory, options, progress);
+    let summary = format!("{}, size: {}", root.path().display(), root.len_str(SizeMeasure::ApparentSize));
+    let saved =
+        File::create(path).map_err(Box::from).and_then(|file| write(BufWriter::new(file), &Snapshot { taken, root }));
+    match saved {
+        Ok(()) => println!("saved {} to {}", summary, path.display()),
+        Err(error) => {
+            Args::command().error(error::ErrorKind::Io, format!("can't save {}: {}", path.display(), error)).exit()
+        }
+    }
+}
+
+fn load_snapshot(path: &Path) -> Snapshot {
+    File::open(path).map_err(Box::from).and_then(read_snapshot).unwrap_or_else(|error| {
+        Args::command().error(error::ErrorKind::Io, format!("can't load {}: {}", path.display(), error)).exit()
+    })
+}
+
+/// An export that doesn't say when it was taken is taken to be as old as its file.
+fn import_ncdu(path: &Path) -> Snapshot {
+    File::open(path)
+        .map_err(Box::from)
+        .and_then(|file| {
+            let modified =
+                file.metadata().and_then(|metadata| metadata.modified()).unwrap_or_else(|_| SystemTime::now());
+            read_ncdu(BufReader::new(file), modified)
+        })
+        .unwrap_or_else(|error| {
+            Args::command().error(error::ErrorKind::Io, format!("can't import {}: {}", path.display(), error)).exit()
+        })
+}
+
+/// Watches with inotify, which is checked to be available before the tree is shown.
+#[cfg(target_os = "linux")]
+fn get_watch(options: Arc<ScanOptions>) -> Box<Watch> {
+    let watcher = RealWatcher::new().unwrap_or_else(|error| {
+        Args::command().error(error::ErrorKind::Io, format!("can't watch for changes: {}", error)).exit()
+    });
+    Box::new(move |requests, tree_links, on_changes| {
+        let progress = ScanProgress::default();
+        watch_folders(watcher, requests, &RealFileOperations, &options, tree_links, &progress, on_changes)
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_watch(_options: Arc<ScanOptions>) -> Box<Watch> {
+    Args::command().error(error::ErrorKind::ArgumentConflict, "--watch is only supported on linux").exit()
+}
+
+/// The default exclusions aren't needed on one file system, as they are all other file systems.
+fn get_exclusions(args: &Args) -> Exclusions {
+    let config = match &args.config {
+        Some(path) => Some(read_config(path)),
+        None => default_config_path().filter(|path| path.is_file()).map(|path| read_config(&path)),
+    };
+    let defaults = if args.one_file_system { vec![] } else { DEFAULT_EXCLUDES.map(String::from).to_vec() };
+    config
+        .transpose()
+        .and_then(|config| {
+            let (config_excludes, config_includes) = config.unwrap_or_default();
+            let excludes = defaults.into_iter().chain(config_excludes).chain(args.exclude.iter().cloned()).collect();
+            let includes: Vec<String> = config_includes.into_iter().chain(args.include.iter().cloned()).collect();
+            Exclusions::new(excludes, &includes)
+        })
+        .unwrap_or_else(|error| Args::command().error(error::ErrorKind::ValueValidation, error).exit())
 }
 
-fn get_arguments() -> (PathBuf, u8, bool, bool) {
+fn get_arguments() -> (PathBuf, Args) {
     let args = Args::parse();
+    // without file ids every target would look like the first one followed
+    #[cfg(target_os = "windows")]
+    if args.follow_symlinks {
+        Args::command().error(error::ErrorKind::ArgumentConflict, "--follow-symlinks isn't supported on Windows").exit()
+    }
+    // or every folder like it is on the same device
+    #[cfg(target_os = "windows")]
+    if args.one_file_system {
+        Args::command().error(error::ErrorKind::ArgumentConflict, "--one-file-system isn't supported on Windows").exit()
+    }
     // todo most of this could be in CLAP validator
     let root_directory = args
         .root_directory
@@ -47,10 +349,14 @@ fn get_arguments() -> (PathBuf, u8, bool, bool) {
         })
         .map(PathBuf::from)
         .filter(|path| path.is_dir())
+        // a snapshot has its own
         .or_else(|| {
+            if args.load.is_some() || args.diff.is_some() || args.import_ncdu.is_some() {
+                return None;
+            }
             eprintln!("supplied argument is not a directory, or no argument supplied. reverting to current directory");
             None
         })
         .unwrap_or_else(|| env::current_dir().expect("error getting `current_dir`"));
-    (root_directory, args.page_size, args.hide_comments, args.show_hidden)
+    (root_directory, args)
 }
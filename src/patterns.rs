@@ -1,6 +1,8 @@
 use lazy_static::lazy_static;
 use regex::{Error, RegexSet};
 
+use crate::file_analysis::file_types::DirectoryEntry;
+
 fn fix_separators(path: &str) -> String {
     #[cfg(not(target_os = "windows"))]
     return path.replace(r"\\", std::path::MAIN_SEPARATOR.to_string().as_str());
@@ -118,3 +120,24 @@ lazy_static! {
         ("Writeable user application data.", RegexSet::new([r"^/var$"])),
     ];
 }
+
+pub(crate) fn get_comment_for_entry(branch: &DirectoryEntry) -> String {
+    let path = match branch {
+        DirectoryEntry::File { path, .. } => path.display().to_string(),
+        DirectoryEntry::Link { path, .. } => path.display().to_string(),
+        DirectoryEntry::Folder { path, .. } => path.display().to_string(),
+        DirectoryEntry::Excluded { path, .. } => path.display().to_string(),
+        DirectoryEntry::MountPoint { path, .. } => path.display().to_string(),
+        // what went wrong rather than a comment
+        DirectoryEntry::Unreadable { message, .. } => return message.clone(),
+        DirectoryEntry::Rollup { .. } => String::from(""),
+    };
+    let mut comment = String::new();
+    for (a_comment, regex_set) in PATTERNS.iter() {
+        if let Ok(true) = regex_set.as_ref().map(|set| set.is_match(&path)) {
+            comment += a_comment;
+            comment += " ";
+        }
+    }
+    comment
+}
@@ -1,27 +1,63 @@
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::fs::{DirEntry, FileType, Metadata, ReadDir};
+use std::fs;
+use std::fs::{DirEntry, File, FileType, Metadata, ReadDir};
+use std::io::Read;
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::MetadataExt;
 #[cfg(target_os = "windows")]
 use std::os::windows::fs::MetadataExt;
-use std::path::PathBuf;
-use std::{fs, mem};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+#[cfg(not(target_os = "windows"))]
+use std::time::{Duration, UNIX_EPOCH};
+
+#[cfg(target_os = "linux")]
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
 
 use crate::file_analysis::file_system_proxy_traits::*;
+use crate::file_analysis::owners::Owner;
+#[cfg(target_os = "linux")]
+use crate::file_analysis::watch::{WatchEvent, WatcherProxy};
 
 pub(crate) struct RealFileOperations;
 
 impl FileSystemProxy for RealFileOperations {
-    fn read_dir(
-        &self, directory: &PathBuf,
-    ) -> Result<Box<dyn ReadDirProxy<Item = Result<Box<dyn DirPathEntryProxy>, Box<dyn Error>>>>, Box<dyn Error>> {
-        let read_dir = fs::read_dir(directory).map_err(|e| FSProxyError { path: directory.clone(), source: e })?;
-        Ok(Box::new(RealReadDir::new(read_dir, directory.clone())))
+    fn read_dir(&self, directory: &Path) -> ReadDirResult {
+        let read_dir =
+            fs::read_dir(directory).map_err(|e| FSProxyError { path: directory.to_path_buf(), source: e })?;
+        Ok(Box::new(RealReadDir { read_dir, path: directory.to_path_buf() }))
     }
-    fn metadata(&self, path: &PathBuf) -> Result<Box<dyn MetadataProxy>, Box<dyn Error>> {
+    fn metadata(&self, path: &Path) -> Result<Box<dyn MetadataProxy>, Box<dyn Error>> {
         Ok(Box::new(RealMetadataProxy {
-            metadata: fs::metadata(path).map_err(|e| FSProxyError { path: path.clone(), source: e })?,
+            metadata: fs::metadata(path).map_err(|e| FSProxyError { path: path.to_path_buf(), source: e })?,
         }))
     }
+    fn read_link(&self, path: &Path) -> Option<PathBuf> { fs::read_link(path).ok() }
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> { fs::canonicalize(path).ok() }
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn Read + Send>> { Ok(Box::new(File::open(path)?)) }
+    #[cfg(target_os = "linux")]
+    fn file_system_type(&self, path: &Path) -> Option<String> {
+        let path = fs::canonicalize(path).ok()?;
+        let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+        // the last mount on a path hides any before it
+        mounts.lines().rev().find_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = fields.nth(1)?;
+            let file_system = fields.next()?;
+            (Path::new(&unescape_mount_point(mount_point)) == path).then(|| file_system.to_string())
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    fn file_system_type(&self, _path: &Path) -> Option<String> { None }
+}
+
+/// Spaces, tabs, newlines and backslashes are octal escaped in /proc/self/mounts.
+#[cfg(target_os = "linux")]
+fn unescape_mount_point(mount_point: &str) -> String {
+    mount_point.replace("\\040", " ").replace("\\011", "\t").replace("\\012", "\n").replace("\\134", "\\")
 }
 
 pub(crate) struct FSProxyError {
@@ -52,20 +88,17 @@ pub(crate) struct RealReadDir {
     path: PathBuf,
 }
 
-impl RealReadDir {
-    fn new(read_dir: ReadDir, directory: PathBuf) -> RealReadDir { Self { read_dir, path: directory } }
-}
-
 impl Iterator for RealReadDir {
-    type Item = Result<Box<dyn DirPathEntryProxy>, Box<dyn Error>>;
+    type Item = DirEntryResult;
     fn next(&mut self) -> Option<Self::Item> {
-        Some(Ok(Box::new(RealDirPathEntry { fs_dir_path: self.read_dir.next()?.unwrap() })))
+        Some(match self.read_dir.next()? {
+            Ok(fs_dir_path) => Ok(Box::new(RealDirPathEntry { fs_dir_path })),
+            Err(source) => Err(Box::new(FSProxyError { path: self.path.clone(), source })),
+        })
     }
 }
 
-impl ReadDirProxy for RealReadDir {
-    fn path(&mut self) -> PathBuf { mem::take(&mut self.path) }
-}
+impl ReadDirProxy for RealReadDir {}
 
 struct RealDirPathEntry {
     fs_dir_path: DirEntry,
@@ -76,14 +109,6 @@ impl DirPathEntryProxy for RealDirPathEntry {
     fn file_type(&self) -> std::io::Result<Box<dyn FileTypeProxy>> {
         Ok(Box::new(RealFileTypeProxy { file_type: self.fs_dir_path.file_type()? }))
     }
-    fn metadata(&self) -> Result<Box<dyn MetadataProxy>, Box<dyn Error>> {
-        Ok(Box::new(RealMetadataProxy {
-            metadata: self
-                .fs_dir_path
-                .metadata()
-                .map_err(|e| FSProxyError { path: self.fs_dir_path.path(), source: e })?,
-        }))
-    }
 }
 
 struct RealFileTypeProxy {
@@ -100,10 +125,129 @@ struct RealMetadataProxy {
 }
 
 impl MetadataProxy for RealMetadataProxy {
+    fn is_dir(&self) -> bool { self.metadata.is_dir() }
+
     fn len(&self) -> u64 { self.metadata.len() }
 
+    // st_blocks is always in 512 byte units, whatever the file system's block size
+    #[cfg(not(target_os = "windows"))]
+    fn disk_len(&self) -> u64 { self.metadata.blocks() * 512 }
+
+    // std doesn't expose the allocation size on Windows
+    #[cfg(target_os = "windows")]
+    fn disk_len(&self) -> u64 { self.metadata.len() }
+
+    #[cfg(not(target_os = "windows"))]
+    fn links(&self) -> u64 { self.metadata.nlink() }
+
+    // std only exposes the link count and file index on Windows in nightly, so every file is treated as unique
+    #[cfg(target_os = "windows")]
+    fn links(&self) -> u64 { 1 }
+
+    #[cfg(not(target_os = "windows"))]
+    fn file_id(&self) -> FileId { FileId { device: self.metadata.dev(), inode: self.metadata.ino() } }
+
+    // so hard links can't be told apart, and --follow-symlinks and --one-file-system are refused
+    #[cfg(target_os = "windows")]
+    fn file_id(&self) -> FileId { FileId::default() }
+
+    fn modified(&self) -> Option<SystemTime> { self.metadata.modified().ok() }
+
+    fn accessed(&self) -> Option<SystemTime> { self.metadata.accessed().ok() }
+
+    #[cfg(not(target_os = "windows"))]
+    fn changed(&self) -> Option<SystemTime> {
+        let nanos = u32::try_from(self.metadata.ctime_nsec()).ok()?;
+        let since_epoch = Duration::new(u64::try_from(self.metadata.ctime()).ok()?, nanos);
+        UNIX_EPOCH.checked_add(since_epoch)
+    }
+
+    #[cfg(target_os = "windows")]
+    fn changed(&self) -> Option<SystemTime> { self.metadata.created().ok() }
+
+    #[cfg(not(target_os = "windows"))]
+    fn owner(&self) -> Option<Owner> { Some(Owner { uid: self.metadata.uid(), gid: self.metadata.gid() }) }
+
+    #[cfg(target_os = "windows")]
+    fn owner(&self) -> Option<Owner> { None }
+
     #[cfg(target_os = "windows")]
     fn file_attributes(&self) -> u32 { self.metadata.file_attributes() }
 }
 
+/// Watches folders with inotify.
+#[cfg(target_os = "linux")]
+pub(crate) struct RealWatcher {
+    inotify: Inotify,
+    folders: HashMap<WatchDescriptor, PathBuf>,
+    buffer: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+impl RealWatcher {
+    pub(crate) fn new() -> std::io::Result<Self> {
+        Ok(RealWatcher { inotify: Inotify::init()?, folders: HashMap::new(), buffer: vec![0; 64 * 1024] })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl WatcherProxy for RealWatcher {
+    fn watch(&mut self, folder: &Path) -> Result<(), Box<dyn Error>> {
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MODIFY
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::DELETE_SELF
+            | WatchMask::ONLYDIR
+            | WatchMask::DONT_FOLLOW;
+        let descriptor = self
+            .inotify
+            .watches()
+            .add(folder, mask)
+            .map_err(|e| FSProxyError { path: folder.to_path_buf(), source: e })?;
+        self.folders.insert(descriptor, folder.to_path_buf());
+        Ok(())
+    }
+
+    fn unwatch(&mut self, folder: &Path) {
+        let descriptors: Vec<WatchDescriptor> =
+            self.folders.iter().filter(|&(_, path)| path == folder).map(|(descriptor, _)| descriptor.clone()).collect();
+        for descriptor in descriptors {
+            self.folders.remove(&descriptor);
+            // fails if the folder has gone, which removed the watch already
+            let _ = self.inotify.watches().remove(descriptor);
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<WatchEvent> {
+        let mut events = vec![];
+        // until there are none waiting, which is reported as an error
+        while let Ok(read) = self.inotify.read_events(&mut self.buffer) {
+            let mut is_empty = true;
+            for event in read {
+                is_empty = false;
+                // including when the queue overflows, so that those changes are missed until rescanned
+                let Some(folder) = self.folders.get(&event.wd) else {
+                    continue;
+                };
+                let path = event.name.map_or_else(|| folder.clone(), |name| folder.join(name));
+                if event.mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                    events.push(WatchEvent::Created(path));
+                } else if event.mask.contains(EventMask::MODIFY) {
+                    events.push(WatchEvent::Modified(path));
+                } else if event.mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM | EventMask::DELETE_SELF) {
+                    events.push(WatchEvent::Deleted(path));
+                } else if event.mask.contains(EventMask::IGNORED) {
+                    self.folders.remove(&event.wd);
+                }
+            }
+            if is_empty {
+                break;
+            }
+        }
+        events
+    }
+}
+
 // todo set of integration tests maybe?
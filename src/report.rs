@@ -0,0 +1,312 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::color::{convert_entry_count_to_color, convert_file_size_to_color};
+use crate::file_analysis::file_types::{DirectoryEntry, SizeMeasure};
+use crate::patterns::get_comment_for_entry;
+
+/// How many levels below the root, and how many entries of each folder, a tree report shows unless told otherwise.
+const DEFAULT_TREE_DEPTH: usize = 3;
+const DEFAULT_TREE_TOP: usize = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+pub(crate) enum ReportFormat {
+    /// An indented tree, coloured by size when written to a terminal
+    Tree,
+    /// One line per entry, with its path, size, type, whether it is hidden and its comment
+    Csv,
+    /// The tree as nested objects
+    Json,
+}
+
+/// What a report includes, `depth` levels below the root and the `top` largest entries of each folder, where none
+/// means all of them, with sizes by `measure`.
+#[derive(Copy, Clone)]
+pub(crate) struct Report {
+    pub(crate) format: ReportFormat,
+    pub(crate) depth: Option<usize>,
+    pub(crate) top: Option<usize>,
+    pub(crate) is_colored: bool,
+    pub(crate) measure: SizeMeasure,
+    pub(crate) show_hidden: bool,
+    pub(crate) hide_comments: bool,
+}
+
+/// An entry of a JSON report.
+#[derive(Serialize)]
+struct ReportEntry {
+    path: String,
+    name: String,
+    size: Option<u64>,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    hidden: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    entries: Vec<ReportEntry>,
+}
+
+/// Writes a report of the tree below `root`, which must be sorted by the measure of `report`. The tree shows rolled
+/// up files as the browser does, the other formats each of them.
+pub(crate) fn write_report(writer: &mut impl Write, root: &DirectoryEntry, report: Report) -> io::Result<()> {
+    match report.format {
+        ReportFormat::Tree => {
+            writeln!(writer, "{} {}", format_size(root, report), root.path().display())?;
+            write_tree(writer, root, "", 1, report)
+        }
+        ReportFormat::Csv => {
+            writeln!(writer, "path,size,type,hidden,comment")?;
+            write_csv(writer, root, 0, report)
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &report_entry(root, 0, report))?;
+            writeln!(writer)
+        }
+    }
+}
+
+/// The entries of `folder` to report, largest first, and how many of those shown there are too many of.
+fn entries_of(folder: &DirectoryEntry, is_unrolled: bool, report: Report) -> (Vec<&DirectoryEntry>, usize) {
+    let mut entries: Vec<&DirectoryEntry> = match is_unrolled {
+        true => folder.unrolled_entries(),
+        false => folder.entries().map(|entries| entries.iter().collect()).unwrap_or_default(),
+    };
+    entries.retain(|entry| report.show_hidden || !entry.is_hidden());
+    // what is in a rollup isn't sorted largest first, and unrolled entries are sorted in among the others
+    entries.sort_by_key(|entry| std::cmp::Reverse(size_of(entry, report.measure)));
+    let top = report.top.unwrap_or(usize::MAX);
+    let more = entries.len().saturating_sub(top);
+    entries.truncate(top);
+    (entries, more)
+}
+
+fn write_tree(
+    writer: &mut impl Write, folder: &DirectoryEntry, indent: &str, depth: usize, report: Report,
+) -> io::Result<()> {
+    if depth > report.depth.unwrap_or(DEFAULT_TREE_DEPTH) {
+        return Ok(());
+    }
+    let report = Report { top: report.top.or(Some(DEFAULT_TREE_TOP)), ..report };
+    let (entries, more) = entries_of(folder, false, report);
+    for (index, entry) in entries.iter().enumerate() {
+        let is_last = index + 1 == entries.len() && more == 0;
+        let (branch, child_indent) = if is_last { ("└─ ", "   ") } else { ("├─ ", "│  ") };
+        let mut line = format!("{} {}{}{}", format_size(entry, report), indent, branch, entry.listed_name());
+        if let Some(comment) = comment_of(entry, report) {
+            line += &format!("  {}", comment);
+        }
+        writeln!(writer, "{}", line)?;
+        write_tree(writer, entry, &format!("{}{}", indent, child_indent), depth + 1, report)?;
+    }
+    if more > 0 {
+        writeln!(writer, "{:>10} {}└─ … {} more", "", indent, more)?;
+    }
+    Ok(())
+}
+
+fn write_csv(writer: &mut impl Write, entry: &DirectoryEntry, depth: usize, report: Report) -> io::Result<()> {
+    let fields = [
+        entry.path().display().to_string(),
+        size_of(entry, report.measure).map_or(String::new(), |size| size.to_string()),
+        kind_of(entry).to_string(),
+        entry.is_hidden().to_string(),
+        comment_of(entry, report).unwrap_or_default(),
+    ];
+    let fields: Vec<String> = fields.iter().map(|field| escape_csv(field)).collect();
+    writeln!(writer, "{}", fields.join(","))?;
+    if depth < report.depth.unwrap_or(usize::MAX) {
+        for child in entries_of(entry, true, report).0 {
+            write_csv(writer, child, depth + 1, report)?;
+        }
+    }
+    Ok(())
+}
+
+fn report_entry(entry: &DirectoryEntry, depth: usize, report: Report) -> ReportEntry {
+    let entries = match depth < report.depth.unwrap_or(usize::MAX) {
+        true => entries_of(entry, true, report).0,
+        false => vec![],
+    };
+    ReportEntry {
+        path: entry.path().display().to_string(),
+        name: entry.name(),
+        size: size_of(entry, report.measure),
+        kind: kind_of(entry),
+        hidden: entry.is_hidden(),
+        comment: comment_of(entry, report),
+        entries: entries.into_iter().map(|child| report_entry(child, depth + 1, report)).collect(),
+    }
+}
+
+/// Sizes are in bytes, or entries, for the formats read by scripts.
+fn size_of(entry: &DirectoryEntry, measure: SizeMeasure) -> Option<u64> { entry.size(measure).map(|size| size.0) }
+
+fn kind_of(entry: &DirectoryEntry) -> &'static str {
+    match entry {
+        DirectoryEntry::File { .. } => "file",
+        DirectoryEntry::Folder { .. } => "dir",
+        DirectoryEntry::Link { .. } => "link",
+        DirectoryEntry::Rollup { .. } => "rollup",
+        DirectoryEntry::Excluded { .. } => "excluded",
+        DirectoryEntry::MountPoint { .. } => "mount",
+        DirectoryEntry::Unreadable { .. } => "unreadable",
+    }
+}
+
+/// None when there is none, or comments are hidden. What went wrong is shown for unreadable entries either way.
+fn comment_of(entry: &DirectoryEntry, report: Report) -> Option<String> {
+    let is_unreadable = matches!(entry, DirectoryEntry::Unreadable { .. });
+    (!report.hide_comments || is_unreadable)
+        .then(|| get_comment_for_entry(entry).trim_end().to_string())
+        .filter(|comment| !comment.is_empty())
+}
+
+/// Right aligned, and coloured as in the browser when the report is.
+fn format_size(entry: &DirectoryEntry, report: Report) -> String {
+    let size = format!("{:>10}", entry.len_str(report.measure));
+    match (report.is_colored, size_of(entry, report.measure)) {
+        (true, Some(bytes)) => {
+            let (r, g, b) = match report.measure {
+                SizeMeasure::EntryCount => convert_entry_count_to_color(bytes),
+                _ => convert_file_size_to_color(bytes),
+            };
+            format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, size)
+        }
+        _ => size,
+    }
+}
+
+/// Quoted only when it has to be.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_analysis::file_types::{DirectoryEntry, SizeMeasure};
+    use crate::file_analysis::mock_utils::{file, folder};
+    use crate::report::{write_report, Report, ReportFormat};
+
+    fn report(format: ReportFormat, depth: Option<usize>, top: Option<usize>, hide_comments: bool) -> String {
+        let root = folder(
+            "current",
+            vec![
+                file(100, "current/a"),
+                file(1000, "current/.hidden"),
+                folder(
+                    "current/steamapps",
+                    vec![folder(
+                        "current/steamapps/Dark Souls III",
+                        vec![file(500, "current/steamapps/Dark Souls III/game.bin")],
+                        false,
+                    )],
+                    false,
+                ),
+                folder(
+                    "current/b,c",
+                    vec![file(30, "current/b,c/d"), file(20, "current/b,c/e"), file(10, "current/b,c/f")],
+                    false,
+                ),
+            ],
+            true,
+        );
+        write(&root, Report { depth, top, hide_comments, ..report_of(format) })
+    }
+
+    fn report_of(format: ReportFormat) -> Report {
+        Report {
+            format,
+            depth: None,
+            top: None,
+            is_colored: false,
+            measure: SizeMeasure::ApparentSize,
+            show_hidden: false,
+            hide_comments: true,
+        }
+    }
+
+    fn write(root: &DirectoryEntry, report: Report) -> String {
+        let mut output = vec![];
+        write_report(&mut output, root, report).expect("written");
+        String::from_utf8(output).expect("UTF-8")
+    }
+
+    #[test]
+    fn test_tree_report() {
+        let expected = [
+            "   1.62 KB current",
+            "     500 B ├─ steamapps/",
+            "     500 B │  └─ Dark Souls III/  A dreadful, awful, beautiful game that will take over your life and make \
+             you doubt your ability to carry out basic human functions.",
+            "     100 B ├─ a",
+            "           └─ … 1 more",
+            "",
+        ];
+        assert_eq!(expected.join("\n"), report(ReportFormat::Tree, Some(2), Some(2), false));
+    }
+
+    #[test]
+    fn test_tree_report_of_rollup() {
+        // files smaller than any folder are rolled up, smallest first
+        let root = folder(
+            "current",
+            vec![
+                file(20, "current/b"),
+                file(30, "current/c"),
+                file(10, "current/a"),
+                folder("current/d", vec![file(100, "current/d/e")], false),
+            ],
+            true,
+        );
+        let expected = [
+            "     160 B current",
+            "     100 B ├─ d/",
+            "     100 B │  └─ <other files...>",
+            "     100 B │     └─ e",
+            "      60 B └─ <other files...>",
+            "      30 B    ├─ c",
+            "      20 B    ├─ b",
+            "              └─ … 1 more",
+            "",
+        ];
+        let report = Report { top: Some(2), ..report_of(ReportFormat::Tree) };
+        assert_eq!(expected.join("\n"), write(&root, report));
+    }
+
+    #[test]
+    fn test_csv_report() {
+        let expected = [
+            "path,size,type,hidden,comment",
+            "current,1660,dir,false,",
+            "current/steamapps,500,dir,false,",
+            "current/steamapps/Dark Souls III,500,dir,false,",
+            "current/a,100,file,false,",
+            "\"current/b,c\",60,dir,false,",
+            "\"current/b,c/d\",30,file,false,",
+            "\"current/b,c/e\",20,file,false,",
+            "\"current/b,c/f\",10,file,false,",
+            "",
+        ];
+        assert_eq!(expected.join("\n"), report(ReportFormat::Csv, Some(2), None, true));
+    }
+
+    #[test]
+    fn test_json_report() {
+        let json: serde_json::Value =
+            serde_json::from_str(&report(ReportFormat::Json, None, Some(1), false)).expect("JSON");
+        assert_eq!(1660, json["size"]);
+        let steamapps = &json["entries"][0];
+        assert_eq!(("steamapps/", "dir"), (steamapps["name"].as_str().unwrap(), steamapps["type"].as_str().unwrap()));
+        let dark_souls = &steamapps["entries"][0];
+        assert!(dark_souls["comment"].as_str().unwrap().starts_with("A dreadful"));
+        assert_eq!(500, dark_souls["entries"][0]["size"]);
+        assert_eq!(1, json["entries"].as_array().unwrap().len());
+    }
+}
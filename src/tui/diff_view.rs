@@ -0,0 +1,176 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use cursive::event::{Event, Key};
+use cursive::theme::BaseColor::Magenta;
+use cursive::theme::{Color, ColorStyle, Effect, Style};
+use cursive::views::{LinearLayout, OnEventView, ScrollView, TextView};
+use cursive::Cursive;
+
+use crate::color::{convert_entry_growth_to_color, convert_growth_to_color};
+use crate::file_analysis::diff::DiffEntry;
+use crate::file_analysis::file_types::{Byteable, SizeMeasure};
+use crate::tui::selectable_text_view::SelectableTextView;
+use crate::tui::{build_theme, format_time_of_day, ViewOptions};
+
+/// Cursive's user data while showing a diff.
+struct DiffState {
+    root: DiffEntry,
+    /// What `root` is currently sorted by.
+    measure: SizeMeasure,
+    /// When the old scan was taken.
+    since: SystemTime,
+}
+
+/// Shows the combined tree of two scans, the old one taken at `since`, sorted and coloured by how much each entry
+/// has grown or shrunk.
+pub(crate) fn display_diff(root: DiffEntry, since: SystemTime, options: ViewOptions) {
+    let mut siv = cursive::default();
+    siv.set_theme(build_theme());
+    let path = root.path.clone();
+    siv.set_user_data(DiffState { root, measure: SizeMeasure::ApparentSize, since });
+    siv.add_global_callback(Key::Esc, |siv| siv.quit());
+    show_diff(options, 0, &path, &mut siv);
+    siv.run();
+}
+
+fn show_diff(options: ViewOptions, page: usize, path: &Path, siv: &mut Cursive) {
+    let Some(state) = siv.user_data::<DiffState>() else {
+        return;
+    };
+    if state.measure != options.measure {
+        state.root.sort_by(options.measure);
+        state.measure = options.measure;
+    }
+    let is_root = state.root.path == path;
+    let since = state.since;
+    if let Some(view) = state.root.find(path).map(|entry| build_diff_views(entry, options, page, is_root, since)) {
+        siv.pop_layer();
+        siv.add_fullscreen_layer(view);
+    }
+}
+
+fn build_diff_views(
+    diff_entry: &DiffEntry, options: ViewOptions, page: usize, is_root: bool, since: SystemTime,
+) -> LinearLayout {
+    let measure = options.measure;
+    // the old and new sizes are shown in place of the counts
+    let row_options = ViewOptions { hide_comments: true, show_counts: true, time_column: None, ..options };
+    let mut entries_layout = LinearLayout::vertical();
+    if let Some(parent) = diff_entry.path.parent().filter(|_| !is_root) {
+        let parent = parent.to_path_buf();
+        entries_layout.add_child(
+            SelectableTextView::new(
+                &parent,
+                "⮬..".to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                Style::from(Effect::Simple),
+                true,
+                row_options,
+                0,
+                Color::Rgb(255, 255, 255),
+            )
+            .on_select(move |siv| show_diff(options, 0, &parent, siv)),
+        );
+    }
+    for (count, entry) in diff_entry.entries.iter().enumerate() {
+        if count >= options.page_size as usize * (page + 1) {
+            let path = diff_entry.path.clone();
+            entries_layout.add_child(
+                SelectableTextView::new(
+                    &path,
+                    "⮯ more…".to_string(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    Style::from(Effect::Simple),
+                    true,
+                    row_options,
+                    page + 1,
+                    Color::Rgb(255, 255, 255),
+                )
+                .on_select(move |siv| show_diff(options, page + 1, &path, siv)),
+            );
+            break;
+        }
+        let path = entry.path.clone();
+        let name = match (entry.is_new(), entry.is_deleted()) {
+            (true, _) => format!("{} (new)", entry.name()),
+            (_, true) => format!("{} (deleted)", entry.name()),
+            _ => entry.name(),
+        };
+        entries_layout.add_child(
+            SelectableTextView::new(
+                &entry.path,
+                name,
+                String::new(),
+                format_change(entry, measure),
+                String::new(),
+                format_growth(entry.growth(measure), measure),
+                Style::from(if entry.is_deleted() {
+                    Effect::Strikethrough
+                } else if entry.is_dir {
+                    Effect::Simple
+                } else {
+                    Effect::Italic
+                }),
+                !entry.entries.is_empty(),
+                row_options,
+                0,
+                color_for_growth(entry.growth(measure), measure),
+            )
+            .on_select(move |siv| show_diff(options, 0, &path, siv)),
+        );
+    }
+
+    let path = diff_entry.path.clone();
+    let event_view = OnEventView::new(ScrollView::new(entries_layout)).on_event(Event::Char('d'), move |siv| {
+        show_diff(ViewOptions { measure: options.measure.cycled(), ..options }, page, &path, siv)
+    });
+    LinearLayout::vertical()
+        .child(
+            TextView::new(
+                "[→←↑↓]: navigate, [Enter]: open, [Space]: open in external app, toggle [d]isk usage/entries, [Esc]: exit",
+            )
+            .style(Style::from(ColorStyle::front(Magenta))),
+        )
+        .child(TextView::new(format!(
+            "{}, {}: {}, {} since {}",
+            diff_entry.path.display(),
+            measure,
+            format_change(diff_entry, measure),
+            format_growth(diff_entry.growth(measure), measure),
+            format_time_of_day(since)
+        )))
+        .child(event_view)
+}
+
+/// The old and new sizes.
+fn format_change(entry: &DiffEntry, measure: SizeMeasure) -> String {
+    format!(
+        "{} → {}",
+        measure.format(Byteable(entry.old_size(measure))),
+        measure.format(Byteable(entry.new_size(measure)))
+    )
+}
+
+fn format_growth(growth: i64, measure: SizeMeasure) -> String {
+    let sign = match growth {
+        0 => "",
+        growth if growth < 0 => "-",
+        _ => "+",
+    };
+    format!("{}{}", sign, measure.format(Byteable(growth.unsigned_abs())))
+}
+
+fn color_for_growth(growth: i64, measure: SizeMeasure) -> Color {
+    let (r, g, b) = match measure {
+        SizeMeasure::EntryCount => convert_entry_growth_to_color(growth),
+        _ => convert_growth_to_color(growth),
+    };
+    Color::Rgb(r, g, b)
+}
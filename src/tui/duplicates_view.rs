@@ -0,0 +1,60 @@
+use cursive::theme::{ColorStyle, Effect, Style};
+use cursive::views::{LinearLayout, TextView};
+
+use crate::file_analysis::duplicates::DuplicateSet;
+use crate::file_analysis::file_types::{Byteable, DirectoryEntry, SizeMeasure};
+use crate::tui::selectable_text_view::SelectableTextView;
+use crate::tui::{color_for_size, show_view, Pane, ViewOptions};
+
+/// Lists the sets of files below `directory_entry` with the same contents, those wasting the most space first, or
+/// that they are still being found. Selecting a copy shows it in its folder.
+pub(crate) fn create_duplicates_layout(
+    directory_entry: &DirectoryEntry, sets: Option<&[DuplicateSet]>, options: ViewOptions,
+) -> LinearLayout {
+    let mut duplicates_layout = LinearLayout::vertical();
+    let Some(sets) = sets else {
+        return duplicates_layout.child(TextView::new("comparing files…"));
+    };
+    if sets.is_empty() {
+        return duplicates_layout.child(TextView::new("no duplicates"));
+    }
+
+    let entries_options = ViewOptions { pane: Pane::Entries, ..options };
+    for set in sets.iter().take(options.page_size as usize) {
+        let color = color_for_size(set.wasted(), SizeMeasure::ApparentSize);
+        duplicates_layout.add_child(
+            TextView::new(format!(
+                "{} copies of {}, wasting {}",
+                set.paths.len(),
+                Byteable(set.len),
+                Byteable(set.wasted())
+            ))
+            .style(ColorStyle::front(color)),
+        );
+        for path in set.paths.iter() {
+            let relative_path = path.strip_prefix(directory_entry.path()).unwrap_or(path);
+            let copy = path.clone();
+            duplicates_layout.add_child(
+                SelectableTextView::new(
+                    path,
+                    format!("  {}", relative_path.display()),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    Byteable(set.len).to_string(),
+                    Style::from(Effect::Simple),
+                    true,
+                    entries_options,
+                    0,
+                    color,
+                )
+                .on_select(move |siv| {
+                    if let Some(folder) = copy.parent() {
+                        show_view(entries_options, 0, folder, Some(&copy), siv);
+                    }
+                }),
+            );
+        }
+    }
+    duplicates_layout
+}
@@ -0,0 +1,97 @@
+use std::cmp::Reverse;
+use std::path::PathBuf;
+
+use cursive::theme::{Color, Effect, Style};
+use cursive::views::{LinearLayout, TextView};
+
+use crate::file_analysis::file_types::{DirectoryEntry, FileTotal};
+use crate::patterns::get_comment_for_entry;
+use crate::tui::selectable_text_view::SelectableTextView;
+use crate::tui::{color_for_size, format_share, format_time, show, Pane, TreeState, ViewOptions};
+
+pub(crate) fn format_extension(extension: &str) -> String {
+    if extension.is_empty() {
+        "(no extension)".to_string()
+    } else {
+        format!(".{}", extension)
+    }
+}
+
+/// Lists how much of `directory_entry` has each extension, largest first. Selecting one lists its largest files.
+pub(crate) fn create_extensions_layout(directory_entry: &DirectoryEntry, options: ViewOptions) -> LinearLayout {
+    let mut extensions_layout = LinearLayout::vertical();
+    let measure = options.measure;
+    let folder_size = directory_entry.size(measure).map_or(0, |size| size.0);
+    let extensions = directory_entry.extensions().unwrap_or_default();
+    let mut totals: Vec<(&String, &FileTotal)> = extensions.0.iter().collect();
+    totals.sort_by_key(|&(_, total)| Reverse(total.size(measure).0));
+
+    for (extension, total) in totals.into_iter().take(options.page_size as usize) {
+        let size = total.size(measure);
+        let path = directory_entry.path().to_path_buf();
+        let selected = extension.clone();
+        let files_options = ViewOptions { pane: Pane::ExtensionFiles, ..options };
+        extensions_layout.add_child(
+            SelectableTextView::new(
+                directory_entry.path(),
+                format_extension(extension),
+                format_share(size.0, folder_size),
+                format!("{} files", total.files),
+                String::new(),
+                measure.format(size),
+                Style::from(Effect::Simple),
+                true,
+                options,
+                0,
+                color_for_size(size.0, measure),
+            )
+            .on_select(move |siv| {
+                if let Some(state) = siv.user_data::<TreeState>() {
+                    state.panes.extension = selected.clone();
+                }
+                show(files_options, 0, &path, siv);
+            }),
+        );
+    }
+    if extensions_layout.is_empty() {
+        extensions_layout.add_child(TextView::new("no files"));
+    }
+    extensions_layout
+}
+
+/// Lists the largest files with `extension` anywhere below `directory_entry`, with their paths from it.
+pub(crate) fn create_extension_files_layout(
+    directory_entry: &DirectoryEntry, extension: &str, options: ViewOptions,
+) -> LinearLayout {
+    let mut files_layout = LinearLayout::vertical().child(SelectableTextView::new(
+        directory_entry.path(),
+        "⮬..".to_string(),
+        String::new(),
+        String::new(),
+        String::new(),
+        "".to_string(),
+        Style::from(Effect::Simple),
+        true,
+        ViewOptions { pane: Pane::Extensions, ..options },
+        0,
+        Color::Rgb(255, 255, 255),
+    ));
+    let measure = options.measure;
+    for file in directory_entry.largest_files(extension, measure, options.page_size as usize) {
+        let relative_path = file.path().strip_prefix(directory_entry.path()).map(PathBuf::from);
+        files_layout.add_child(SelectableTextView::new(
+            file.path(),
+            relative_path.unwrap_or_else(|_| file.path().to_path_buf()).display().to_string(),
+            get_comment_for_entry(file),
+            String::new(),
+            options.time_column.and_then(|kind| file.times().get(kind)).map_or(String::new(), format_time),
+            file.len_str(measure),
+            Style::from(Effect::Italic),
+            false,
+            options,
+            0,
+            file.size(measure).map_or(Color::Rgb(255, 255, 255), |size| color_for_size(size.0, measure)),
+        ));
+    }
+    files_layout
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use cursive::theme::BaseColor::Magenta;
+use cursive::theme::{Color, ColorStyle, Effect, Style};
+use cursive::views::{LinearLayout, TextView};
+
+use crate::file_analysis::file_types::DirectoryEntry;
+use crate::patterns::get_comment_for_entry;
+use crate::tui::selectable_text_view::SelectableTextView;
+use crate::tui::{color_for_size, format_time, show_view, Pane, ViewOptions};
+
+/// Lists the largest files anywhere below `directory_entry`, and the largest folders without folders of their own,
+/// with their full paths. Selecting one shows the folder it is in, with it selected.
+pub(crate) fn create_largest_layout(directory_entry: &DirectoryEntry, options: ViewOptions) -> LinearLayout {
+    let measure = options.measure;
+    let (files, folders) = directory_entry.largest_entries(measure, options.page_size as usize, options.show_hidden);
+    let mut largest_layout = LinearLayout::vertical();
+    for (title, entries, style) in [("files", files, Effect::Italic), ("folders", folders, Effect::Simple)] {
+        largest_layout
+            .add_child(TextView::new(format!("largest {}:", title)).style(Style::from(ColorStyle::front(Magenta))));
+        if entries.is_empty() {
+            largest_layout.add_child(TextView::new(format!("no {}", title)));
+        }
+        for entry in entries {
+            let path = entry.path().to_path_buf();
+            let entries_options = ViewOptions { pane: Pane::Entries, ..options };
+            largest_layout.add_child(
+                SelectableTextView::new(
+                    entry.path(),
+                    entry.path().display().to_string(),
+                    get_comment_for_entry(entry),
+                    String::new(),
+                    options.time_column.and_then(|kind| entry.times().get(kind)).map_or(String::new(), format_time),
+                    entry.len_str(measure),
+                    Style::from(style),
+                    true,
+                    options,
+                    0,
+                    entry.size(measure).map_or(Color::Rgb(255, 255, 255), |size| color_for_size(size.0, measure)),
+                )
+                .on_select(move |siv| {
+                    let parent = path.parent().unwrap_or(Path::new(""));
+                    show_view(entries_options, 0, parent, Some(&path), siv)
+                }),
+            );
+        }
+    }
+    largest_layout
+}
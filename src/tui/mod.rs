@@ -1,203 +1,793 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Local};
 
 use cursive::event::{Event, Key};
 use cursive::theme::BaseColor::Magenta;
 use cursive::theme::{BorderStyle, Color, ColorStyle, Effect, Palette, Style, Theme};
-use cursive::views::{LinearLayout, OnEventView, ScrollView, TextView};
+use cursive::view::Nameable;
+use cursive::views::{LinearLayout, NamedView, OnEventView, ScrollView, TextView};
 use cursive::{Cursive, With};
 
-use color::convert_file_size_to_color;
+pub(crate) use diff_view::display_diff;
+use duplicates_view::create_duplicates_layout;
+use extensions_view::{create_extension_files_layout, create_extensions_layout, format_extension};
+use largest_view::create_largest_layout;
+use owners_view::create_owners_layout;
 use selectable_text_view::SelectableTextView;
+use treemap_view::create_treemap_layout;
 
-use crate::file_analysis::file_types::{Byteable, DirectoryEntry};
-use crate::tui::patterns::PATTERNS;
+use crate::color::{convert_entry_count_to_color, convert_file_size_to_color};
+use crate::file_analysis::duplicates::DuplicateSet;
+use crate::file_analysis::file_system_proxy_traits::FileId;
+use crate::file_analysis::file_types::{Byteable, DirectoryEntry, EntryCounts, SizeMeasure, TimeKind};
+use crate::file_analysis::progress::ScanProgress;
+use crate::file_analysis::snapshot::Snapshot;
+use crate::file_analysis::watch::{TreeChange, TreeLinks};
+use crate::patterns::get_comment_for_entry;
+use crate::tui::progress_view::ScanProgressView;
 
-mod color;
-mod patterns;
+mod diff_view;
+mod duplicates_view;
+mod extensions_view;
+mod largest_view;
+mod owners_view;
+mod progress_view;
 mod selectable_text_view;
+mod treemap_view;
+
+const ENTRIES_NAME: &str = "entries";
+const REDRAW_INTERVAL: Duration = Duration::from_millis(500);
+/// The most folders watched for changes, roughly the least that inotify allows by default. In larger trees only the
+/// current folder is.
+const MAX_WATCHED_FOLDERS: usize = 8192;
+
+#[derive(Clone, Copy)]
+pub(crate) struct ViewOptions {
+    pub(crate) page_size: u8,
+    pub(crate) hide_comments: bool,
+    pub(crate) show_hidden: bool,
+    pub(crate) measure: SizeMeasure,
+    pub(crate) show_counts: bool,
+    /// Whether to show what share each entry is of its folder, and of the whole tree.
+    pub(crate) show_percentages: bool,
+    /// Whether to show a bar as long as the share each entry is of its folder.
+    pub(crate) show_graph: bool,
+    /// Which time to show a column of, if any.
+    pub(crate) time_column: Option<TimeKind>,
+    /// Whether to show only what hasn't been modified for `stale_age`.
+    pub(crate) only_stale: bool,
+    pub(crate) stale_age: Duration,
+    pub(crate) pane: Pane,
+}
+
+/// What is shown of the current folder.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Pane {
+    Entries,
+    /// Who owns its files.
+    Owners,
+    /// How much of it has each extension.
+    Extensions,
+    /// Its largest files with the selected extension.
+    ExtensionFiles,
+    /// The files below it with the same contents.
+    Duplicates,
+    /// The largest files and folders anywhere in the tree, shown for the root.
+    Largest,
+    /// The entries as a treemap.
+    Treemap,
+}
+
+impl ViewOptions {
+    fn stale_since(&self) -> Option<SystemTime> {
+        self.only_stale.then(|| SystemTime::now().checked_sub(self.stale_age)).flatten()
+    }
+}
+
+/// Scans the tree below a path, which is the root of the whole tree if the flag is set, sending updates to the
+/// callback as `read_fs` does, where the files with more than one hard link in the set are counted elsewhere already.
+pub(crate) type Scan = dyn Fn(PathBuf, bool, HashSet<FileId>, &(dyn Fn(DirectoryEntry) + Sync)) + Send + Sync;
+
+/// Where the tree comes from.
+pub(crate) enum Source {
+    /// Scanning the tree below the root directory, which can be scanned again.
+    Scan(PathBuf, Arc<Scan>),
+    /// A snapshot of a scanned tree, which is browsed without touching the file system.
+    Snapshot(Box<Snapshot>),
+}
+
+/// Finds which of the given files, with their lengths, have the same contents, as `find_duplicates` does.
+pub(crate) type FindDuplicates = dyn Fn(Vec<(PathBuf, u64)>) -> Vec<DuplicateSet> + Send + Sync;
 
-pub(crate) fn display_result(root_entry: DirectoryEntry, page_size: u8, hide_comments: bool, show_hidden: bool) {
+/// Watches the folders last received for changes, passing them to the callback, as `watch_folders` does.
+pub(crate) type Watch = dyn FnOnce(Receiver<Vec<PathBuf>>, &TreeLinks, &dyn Fn(Vec<TreeChange>)) + Send;
+
+/// Cursive's user data. The scan keeps splicing updates into `root` while it is being browsed, so what is currently
+/// shown is kept to redraw it.
+struct TreeState {
+    root: DirectoryEntry,
+    /// What `root` is currently sorted and rolled up by.
+    measure: SizeMeasure,
+    progress: Arc<ScanProgress>,
+    /// None when browsing a snapshot, which isn't scanned.
+    scan: Option<Arc<Scan>>,
+    /// When the snapshot being browsed was taken.
+    snapshot_taken: Option<SystemTime>,
+    find_duplicates: Arc<FindDuplicates>,
+    /// Until it is started, once the tree has been scanned.
+    watch: Option<Box<Watch>>,
+    watcher: Option<Watcher>,
+    current: CurrentView,
+    needs_redraw: bool,
+    last_drawn: Option<Instant>,
+    panes: PaneState,
+}
+
+/// What the panes other than `Pane::Entries` show that isn't in the tree.
+#[derive(Default)]
+pub(crate) struct PaneState {
+    /// The extension whose files are listed by `Pane::ExtensionFiles`.
+    extension: String,
+    /// The duplicates found below a folder, which are none until they have all been found.
+    duplicates: Option<(PathBuf, Option<Vec<DuplicateSet>>)>,
+}
+
+/// Where to send which folders to watch, and whether that is only the current one as there are too many.
+struct Watcher {
+    folders: Sender<Vec<PathBuf>>,
+    is_current_only: bool,
+}
+
+/// What the header says about the tree as a whole.
+#[derive(Default)]
+pub(crate) struct TreeStatus {
+    /// The progress of the scan, while there is one.
+    progress: Option<Arc<ScanProgress>>,
+    is_watching: bool,
+    /// The root and when the snapshot being browsed was taken.
+    snapshot: Option<(PathBuf, SystemTime)>,
+    /// The size of the whole tree, which percentages of the root are of.
+    root_size: Option<u64>,
+}
+
+#[derive(Clone)]
+struct CurrentView {
+    path: PathBuf,
+    page: usize,
+    options: ViewOptions,
+}
+
+/// Shows a progress screen until the scan from `source`, run on a background thread and reporting to `progress`,
+/// sends the root folder. After that the tree can be browsed while the rest of it is scanned. A snapshot is shown
+/// straight away.
+pub(crate) fn display_result(
+    source: Source, find_duplicates: Arc<FindDuplicates>, watch: Option<Box<Watch>>, progress: Arc<ScanProgress>,
+    options: ViewOptions,
+) {
     let mut siv = cursive::default();
     siv.set_theme(build_theme());
-    if let Some(view) = build_views(&root_entry, page_size, 0, true, hide_comments, show_hidden) {
-        siv.add_fullscreen_layer(view);
-        siv.set_user_data(root_entry);
-        siv.add_global_callback(Key::Esc, |siv| siv.quit());
-        siv.run();
+    siv.add_fullscreen_layer(
+        LinearLayout::vertical()
+            .child(TextView::new("[Esc]: cancel and exit").style(Style::from(ColorStyle::front(Magenta))))
+            .child(ScanProgressView::new(progress.clone())),
+    );
+    let (root, scan, snapshot_taken) = match source {
+        Source::Scan(root_directory, scan) => {
+            (DirectoryEntry::new_scanning_folder(root_directory, false), Some(scan), None)
+        }
+        Source::Snapshot(snapshot) => {
+            let Snapshot { taken, root } = *snapshot;
+            (root, None, Some(taken))
+        }
+    };
+    let root_directory = root.path().to_path_buf();
+    siv.set_user_data(TreeState {
+        root,
+        // as the scanner sorts it, which snapshots are saved from
+        measure: SizeMeasure::ApparentSize,
+        progress: progress.clone(),
+        scan,
+        snapshot_taken,
+        find_duplicates,
+        watch,
+        watcher: None,
+        current: CurrentView { path: root_directory.clone(), page: 0, options },
+        needs_redraw: false,
+        last_drawn: None,
+        panes: PaneState::default(),
+    });
+    siv.add_global_callback(Event::Refresh, |siv| redraw(siv, false));
+    siv.add_global_callback(Key::Esc, move |siv| {
+        progress.cancel();
+        siv.quit()
+    });
+    if snapshot_taken.is_some() {
+        show(options, 0, &root_directory, &mut siv);
+    } else {
+        start_scan(&mut siv, root_directory, true, HashSet::new());
+    }
+    siv.run();
+}
+
+/// Runs the scan of `path` on a background thread, splicing its updates into the tree. The files with more than one
+/// hard link in `counted_links` are counted elsewhere in the tree.
+fn start_scan(siv: &mut Cursive, path: PathBuf, is_root: bool, counted_links: HashSet<FileId>) {
+    let scan = siv.user_data::<TreeState>().and_then(|state| {
+        // the progress is of this scan
+        state.progress.reset();
+        state.scan.clone()
+    });
+    if let Some(scan) = scan {
+        siv.set_autorefresh(true);
+        let cb_sink = siv.cb_sink().clone();
+        thread::spawn(move || {
+            // sending only fails if the user has already exited
+            scan(path, is_root, counted_links, &|update| {
+                let _ = cb_sink.send(Box::new(move |siv| apply_update(siv, update)));
+            });
+            let _ = cb_sink.send(Box::new(|siv| {
+                // the current view's header still shows the progress
+                let is_scanning = siv.user_data::<TreeState>().is_some_and(|state| {
+                    state.needs_redraw = true;
+                    state.root.is_scanning()
+                });
+                if !is_scanning {
+                    watch_folders(siv);
+                }
+                // changes are applied as they are watched, then redrawn on refresh
+                let is_watching = siv.user_data::<TreeState>().is_some_and(|state| state.watcher.is_some());
+                siv.set_autorefresh(is_scanning || is_watching);
+                redraw(siv, true);
+            }));
+        });
+    }
+}
+
+fn apply_update(siv: &mut Cursive, mut update: DirectoryEntry) {
+    if let Some(state) = siv.user_data::<TreeState>() {
+        let current_path = &state.current.path;
+        if update.path().starts_with(current_path) || current_path.starts_with(update.path()) {
+            state.needs_redraw = true;
+        }
+        // the scanner sorts by apparent size
+        if state.measure != SizeMeasure::ApparentSize {
+            update.sort_by(state.measure);
+        }
+        state.root.replace(update, state.measure);
+    }
+}
+
+/// Watches the whole tree for changes if it isn't too large, otherwise only the current folder, starting to once it
+/// has first been scanned.
+fn watch_folders(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let Some(state) = siv.user_data::<TreeState>() else {
+        return;
+    };
+    if let Some(watch) = state.watch.take() {
+        let (folders, requests) = mpsc::channel();
+        state.watcher = Some(Watcher { folders, is_current_only: false });
+        thread::spawn(move || {
+            // sending only fails if the user has already exited, when nothing is counted anyway
+            let links_sink = cb_sink.clone();
+            let tree_links = move |path: &Path| {
+                let (sender, tree_links) = mpsc::channel();
+                let path = path.to_path_buf();
+                let _ = links_sink.send(Box::new(move |siv| {
+                    let state = siv.user_data::<TreeState>();
+                    let _ = sender.send(state.map(|state| state.root.links_of(&path)).unwrap_or_default());
+                }));
+                tree_links.recv().unwrap_or_default()
+            };
+            watch(requests, &tree_links, &|changes| {
+                let _ = cb_sink.send(Box::new(move |siv| apply_changes(siv, changes)));
+            })
+        });
+    }
+    if let Some(watcher) = state.watcher.as_mut() {
+        let mut folders: Vec<PathBuf> = state.root.folders().into_iter().map(Path::to_path_buf).collect();
+        watcher.is_current_only = folders.len() > MAX_WATCHED_FOLDERS;
+        if watcher.is_current_only {
+            folders = vec![state.current.path.clone()];
+        }
+        // sending only fails if watching has failed
+        let _ = watcher.folders.send(folders);
+    }
+}
+
+fn apply_changes(siv: &mut Cursive, changes: Vec<TreeChange>) {
+    if let Some(state) = siv.user_data::<TreeState>() {
+        for change in changes {
+            let path = match change {
+                TreeChange::Changed(entry) => {
+                    let mut entry = *entry;
+                    let path = entry.path().to_path_buf();
+                    // the scanner sorts by apparent size
+                    if state.measure != SizeMeasure::ApparentSize {
+                        entry.sort_by(state.measure);
+                    }
+                    state.root.insert(entry, state.measure).then_some(path)
+                }
+                TreeChange::Removed(path) => state.root.remove(&path, state.measure).then_some(path),
+            };
+            if let Some(path) = path {
+                state.needs_redraw = true;
+                // the copies found may have changed too
+                if state.panes.duplicates.as_ref().is_some_and(|(found_path, _)| path.starts_with(found_path)) {
+                    state.panes.duplicates = None;
+                }
+            }
+        }
+        // the current folder may have been removed
+        while state.root.find(&state.current.path).is_none() {
+            match state.current.path.parent() {
+                Some(parent) if parent.starts_with(state.root.path()) => {
+                    state.current = CurrentView { path: parent.to_path_buf(), page: 0, options: state.current.options };
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+fn redraw(siv: &mut Cursive, force: bool) {
+    let current = siv.user_data::<TreeState>().and_then(|state| {
+        let is_due = state.last_drawn.is_none_or(|last_drawn| last_drawn.elapsed() >= REDRAW_INTERVAL);
+        if state.needs_redraw && (is_due || force) {
+            state.needs_redraw = false;
+            state.last_drawn = Some(Instant::now());
+            Some(state.current.clone())
+        } else {
+            None
+        }
+    });
+    if let Some(current) = current {
+        let focus = siv.call_on_name(ENTRIES_NAME, |entries: &mut LinearLayout| focused_path(entries)).flatten();
+        show_view(current.options, current.page, &current.path, focus.as_deref(), siv);
     }
 }
 
+fn focused_path(entries: &LinearLayout) -> Option<PathBuf> {
+    child_path(entries, entries.get_focus_index()).map(Path::to_path_buf)
+}
+
+fn child_path(entries: &LinearLayout, index: usize) -> Option<&Path> {
+    entries.get_child(index).and_then(|child| child.downcast_ref::<SelectableTextView>()).map(|child| child.path())
+}
+
 pub(crate) fn build_views(
-    directory_entry: &DirectoryEntry, page_size: u8, page: usize, is_root: bool, hide_comments: bool, show_hidden: bool,
+    directory_entry: &DirectoryEntry, options: ViewOptions, page: usize, is_root: bool, status: TreeStatus,
+    focus: Option<&Path>, panes: &PaneState,
 ) -> Option<LinearLayout> {
     directory_entry.entries().map(|entries| {
-        let root_layout = create_root_layout(directory_entry);
+        let is_snapshot = status.snapshot.is_some();
+        let root_size = status.root_size;
+        let root_layout = create_root_layout(directory_entry, options, status, panes);
 
-        let entries_layout =
-            create_entries_layout(directory_entry, page_size, page, is_root, hide_comments, show_hidden, entries);
+        let entries_layout = match options.pane {
+            Pane::Entries => create_entries_layout(directory_entry, options, page, is_root, entries, focus, root_size),
+            Pane::Owners => create_owners_layout(directory_entry, options),
+            Pane::Extensions => create_extensions_layout(directory_entry, options),
+            Pane::ExtensionFiles => create_extension_files_layout(directory_entry, &panes.extension, options),
+            Pane::Duplicates if is_snapshot => {
+                LinearLayout::vertical().child(TextView::new("the contents of files aren't kept in snapshots"))
+            }
+            Pane::Duplicates => {
+                let sets = panes.duplicates.as_ref().and_then(|(_, sets)| sets.as_deref());
+                create_duplicates_layout(directory_entry, sets, options)
+            }
+            Pane::Largest => create_largest_layout(directory_entry, options),
+            Pane::Treemap => create_treemap_layout(directory_entry, options, is_root),
+        };
 
-        let event_view =
-            register_event_listeners(directory_entry, page_size, page, hide_comments, show_hidden, entries_layout);
+        let event_view = register_event_listeners(directory_entry, options, page, entries_layout);
 
         root_layout.child(event_view)
     })
 }
 
 fn register_event_listeners(
-    directory_entry: &DirectoryEntry, page_size: u8, page: usize, hide_comments: bool, show_hidden: bool,
-    entries_layout: LinearLayout,
-) -> OnEventView<ScrollView<LinearLayout>> {
-    let view = OnEventView::new(ScrollView::new(entries_layout));
+    directory_entry: &DirectoryEntry, options: ViewOptions, page: usize, entries_layout: LinearLayout,
+) -> OnEventView<ScrollView<NamedView<LinearLayout>>> {
+    let mut view = OnEventView::new(ScrollView::new(entries_layout.with_name(ENTRIES_NAME)));
+    let path = directory_entry.path();
 
-    let path = directory_entry.path().to_path_buf();
-    let path2 = path.clone();
+    on_key(&mut view, 'c', path, move |siv, path| {
+        show(ViewOptions { hide_comments: !options.hide_comments, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 's', path, move |siv, path| {
+        show(ViewOptions { show_hidden: !options.show_hidden, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'd', path, move |siv, path| {
+        show(ViewOptions { measure: options.measure.cycled(), ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'n', path, move |siv, path| {
+        show(ViewOptions { show_counts: !options.show_counts, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'e', path, move |siv, path| {
+        show(ViewOptions { show_percentages: !options.show_percentages, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'g', path, move |siv, path| {
+        show(ViewOptions { show_graph: !options.show_graph, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'm', path, move |siv, path| {
+        show(ViewOptions { time_column: TimeKind::next(options.time_column), ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'o', path, move |siv, path| {
+        show(ViewOptions { only_stale: !options.only_stale, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'u', path, move |siv, path| {
+        let pane = if options.pane == Pane::Owners { Pane::Entries } else { Pane::Owners };
+        show(ViewOptions { pane, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 't', path, move |siv, path| {
+        let pane = if options.pane == Pane::Entries || options.pane == Pane::Owners {
+            Pane::Extensions
+        } else {
+            Pane::Entries
+        };
+        show(ViewOptions { pane, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'p', path, move |siv, path| {
+        let pane = if options.pane == Pane::Duplicates { Pane::Entries } else { Pane::Duplicates };
+        show(ViewOptions { pane, ..options }, page, path, siv);
+    });
+    on_key(&mut view, 'l', path, move |siv, path| {
+        let pane = if options.pane == Pane::Largest { Pane::Entries } else { Pane::Largest };
+        // of the whole tree
+        let root = siv.user_data::<TreeState>().map(|state| state.root.path().to_path_buf());
+        show(ViewOptions { pane, ..options }, 0, root.as_deref().unwrap_or(path), siv);
+    });
+    on_key(&mut view, 'v', path, move |siv, path| {
+        let pane = if options.pane == Pane::Treemap { Pane::Entries } else { Pane::Treemap };
+        show(ViewOptions { pane, ..options }, 0, path, siv);
+    });
+    on_key(&mut view, 'r', path, move |siv, path| {
+        let focus = siv.call_on_name(ENTRIES_NAME, |entries: &mut LinearLayout| focused_path(entries)).flatten();
+        rescan(focus.as_deref(), path, siv);
+        show_view(options, page, path, focus.as_deref(), siv);
+    });
+    view
+}
 
-    view.on_event(Event::Char('c'), move |siv| {
-        show(page_size, page, !hide_comments, show_hidden, &path, siv);
-    })
-    .on_event(Event::Char('s'), move |siv| {
-        show(page_size, page, hide_comments, !show_hidden, &path2, siv);
-    })
+/// Calls `on_press` with its own copy of `path` when `key` is pressed.
+fn on_key<T: 'static>(
+    view: &mut OnEventView<T>, key: char, path: &Path, on_press: impl Fn(&mut Cursive, &Path) + Send + Sync + 'static,
+) {
+    let path = path.to_path_buf();
+    view.set_on_event(Event::Char(key), move |siv| on_press(siv, &path));
 }
 
-fn show(page_size: u8, page: usize, hide_comments: bool, show_hidden: bool, path: &PathBuf, siv: &mut Cursive) {
-    if let Some(found_entry) = siv.user_data::<DirectoryEntry>().and_then(|entry| entry.find(path)) {
-        if let Some(view) = build_views(found_entry, page_size, page, found_entry.is_root(), hide_comments, show_hidden)
-        {
+fn show(options: ViewOptions, page: usize, path: &Path, siv: &mut Cursive) { show_view(options, page, path, None, siv) }
+
+fn show_view(options: ViewOptions, page: usize, path: &Path, focus: Option<&Path>, siv: &mut Cursive) {
+    enter_unscanned(path, siv);
+    if options.pane == Pane::Duplicates {
+        start_finding_duplicates(path, siv);
+    }
+    if let Some(state) = siv.user_data::<TreeState>() {
+        if state.measure != options.measure {
+            state.root.sort_by(options.measure);
+            state.measure = options.measure;
+        }
+        let status = TreeStatus {
+            progress: state.root.is_scanning().then(|| state.progress.clone()),
+            is_watching: state.watcher.is_some(),
+            snapshot: state.snapshot_taken.map(|taken| (state.root.path().to_path_buf(), taken)),
+            root_size: state.root.size(options.measure).map(|size| size.0),
+        };
+        if let Some(view) = state.root.find(path).and_then(|found_entry| {
+            build_views(found_entry, options, page, found_entry.is_root(), status, focus, &state.panes)
+        }) {
+            if let Some(watcher) = state.watcher.as_ref().filter(|watcher| watcher.is_current_only) {
+                if state.current.path != path {
+                    // sending only fails if watching has failed
+                    let _ = watcher.folders.send(vec![path.to_path_buf()]);
+                }
+            }
+            state.current = CurrentView { path: path.to_path_buf(), page, options };
             siv.pop_layer();
             siv.add_fullscreen_layer(view);
         }
     }
 }
 
+/// Finds the duplicates below `path` on a background thread, unless they have been already. Only what has been
+/// scanned so far is compared.
+fn start_finding_duplicates(path: &Path, siv: &mut Cursive) {
+    let Some(state) = siv.user_data::<TreeState>().filter(|state| state.scan.is_some()) else {
+        return;
+    };
+    if state.panes.duplicates.as_ref().is_some_and(|(found_path, _)| found_path == path) {
+        return;
+    }
+    let Some(files) = state.root.find(path).map(DirectoryEntry::duplicate_candidates) else {
+        return;
+    };
+    state.panes.duplicates = Some((path.to_path_buf(), None));
+    let find_duplicates = state.find_duplicates.clone();
+    let cb_sink = siv.cb_sink().clone();
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        let sets = find_duplicates(files);
+        // sending only fails if the user has already exited
+        let _ = cb_sink.send(Box::new(move |siv| {
+            if let Some(state) = siv.user_data::<TreeState>() {
+                if let Some((found_path, found_sets)) = state.panes.duplicates.as_mut() {
+                    if *found_path == path {
+                        *found_sets = Some(sets);
+                        state.needs_redraw = true;
+                    }
+                }
+            }
+            redraw(siv, true);
+        }));
+    });
+}
+
+/// Scans the `selected` folder again, or the `current` one if what is selected isn't a folder below it, replacing
+/// what was in the tree.
+fn rescan(selected: Option<&Path>, current: &Path, siv: &mut Cursive) {
+    let rescanned = siv.user_data::<TreeState>().filter(|state| state.scan.is_some()).and_then(|state| {
+        let is_folder = |path: &&Path| matches!(state.root.find(path), Some(DirectoryEntry::Folder { .. }));
+        // the back entry selects the parent
+        let path = selected.filter(|path| path.starts_with(current)).filter(is_folder).unwrap_or(current);
+        let (is_root, is_hidden) = state.root.find(path).map(|entry| (entry.is_root(), entry.is_hidden()))?;
+        let mut placeholder = DirectoryEntry::new_scanning_folder(path.to_path_buf(), is_hidden);
+        if let DirectoryEntry::Folder { is_root: placeholder_is_root, .. } = &mut placeholder {
+            *placeholder_is_root = is_root;
+        }
+        // the copies found may have changed too
+        state.panes.duplicates = None;
+        let is_replaced = state.root.replace(placeholder, state.measure);
+        is_replaced.then(|| (path.to_path_buf(), is_root, state.root.counted_links(Some(path))))
+    });
+    if let Some((path, is_root, counted_links)) = rescanned {
+        start_scan(siv, path, is_root, counted_links);
+    }
+}
+
+/// Starts scanning the mount point or collapsed folder at `path`, if that's what it is.
+fn enter_unscanned(path: &Path, siv: &mut Cursive) {
+    let state = siv.user_data::<TreeState>().filter(|state| state.scan.is_some());
+    let counted_links = state.and_then(|state| {
+        let is_entered = match state.root.find(path) {
+            Some(&DirectoryEntry::MountPoint { is_hidden, .. })
+            | Some(&DirectoryEntry::Folder { is_hidden, is_collapsed: true, .. }) => {
+                state.root.replace(DirectoryEntry::new_scanning_folder(path.to_path_buf(), is_hidden), state.measure)
+            }
+            _ => false,
+        };
+        is_entered.then(|| state.root.counted_links(Some(path)))
+    });
+    if let Some(counted_links) = counted_links {
+        start_scan(siv, path.to_path_buf(), false, counted_links);
+    }
+}
+
 fn create_entries_layout(
-    directory_entry: &DirectoryEntry, page_size: u8, page: usize, is_root: bool, hide_comments: bool,
-    show_hidden: bool, entries: &Vec<DirectoryEntry>,
+    directory_entry: &DirectoryEntry, options: ViewOptions, page: usize, is_root: bool, entries: &[DirectoryEntry],
+    focus: Option<&Path>, root_size: Option<u64>,
 ) -> LinearLayout {
     let mut entries_layout = LinearLayout::vertical();
     if !is_root {
-        if let Some(back) = create_back_entry(directory_entry, page_size, hide_comments, show_hidden) {
+        if let Some(back) = create_back_entry(directory_entry, options) {
             entries_layout.add_child(back)
         }
     }
 
-    let mut count = 0;
-    for branch in entries.iter() {
-        if count >= page_size as usize * (page + 1) {
-            entries_layout.add_child(create_more_entry(
-                directory_entry.path(),
-                page_size,
-                page,
-                hide_comments,
-                show_hidden,
-            ));
+    // only what is stale, sorted by how much of it is
+    let stale_since = options.stale_since();
+    let mut shown: Vec<(&DirectoryEntry, Option<u64>)> = entries
+        .iter()
+        .filter(|branch| !branch.is_hidden() || options.show_hidden)
+        .map(|branch| (branch, stale_since.map(|since| branch.stale_size(since, options.measure))))
+        .filter(|&(_, stale_size)| stale_size != Some(0))
+        .collect();
+    if stale_since.is_some() {
+        shown.sort_by_key(|&(_, stale_size)| Reverse(stale_size));
+    }
+
+    for (count, (branch, stale_size)) in shown.into_iter().enumerate() {
+        if count >= options.page_size as usize * (page + 1) {
+            entries_layout.add_child(create_more_entry(directory_entry.path(), options, page));
             break;
         }
-        if !branch.is_hidden() || show_hidden {
-            entries_layout.add_child(create_view_entry(branch, page_size, hide_comments, show_hidden));
-            count += 1;
-        }
+        let size = stale_size.or_else(|| branch.size(options.measure).map(|size| size.0));
+        let parent_size = directory_entry.size(options.measure).map(|size| size.0);
+        entries_layout.add_child(create_view_entry(branch, options, stale_size).with_shares(
+            size,
+            parent_size,
+            root_size,
+        ));
+    }
+
+    // what is on a later page focuses the more entry, which is last
+    let child_index =
+        |path: &Path| (0..entries_layout.len()).find(|&index| child_path(&entries_layout, index) == Some(path));
+    let last_index = entries_layout.len().checked_sub(1);
+    if let Some(focus_index) = focus.and_then(|focus| {
+        child_index(focus).or_else(|| {
+            last_index.filter(|&index| {
+                focus.parent() == Some(directory_entry.path())
+                    && child_path(&entries_layout, index) == Some(directory_entry.path())
+            })
+        })
+    }) {
+        let _ = entries_layout.set_focus_index(focus_index);
     }
     entries_layout
 }
 
-fn create_root_layout(directory_entry: &DirectoryEntry) -> LinearLayout {
-    LinearLayout::vertical()
+fn create_root_layout(
+    directory_entry: &DirectoryEntry, options: ViewOptions, status: TreeStatus, panes: &PaneState,
+) -> LinearLayout {
+    let measure = options.measure;
+    let root_layout = LinearLayout::vertical()
         .child(
-            TextView::new("[→←↑↓]: navigate, [Enter]: open, [Space]: open in external app, toggle [c]omments, [s]how hidden, [Esc]: exit")
+            TextView::new("[→←↑↓]: navigate, [Enter]: open, [Space]: open in external app, toggle [c]omments, [s]how hidden, [d]isk usage/entries, cou[n]ts, p[e]rcentages, bar [g]raph, last [m]odified, [o]ld only, [r]escan, [u]sers, file [t]ypes, du[p]licates, [l]argest, treemap [v]iew, [Esc]: exit")
                 .style(Style::from(ColorStyle::front(Magenta))),
         )
-        .child(TextView::new(format!("{}, size: {}", directory_entry.path().display(), directory_entry.len().unwrap_or(&Byteable(0)))))
+        .child(TextView::new(format!(
+            "{}, {}: {}{}{}{}{}{}{}",
+            directory_entry.path().display(),
+            measure,
+            directory_entry.len_str(measure),
+            match directory_entry.errors() {
+                0 => String::new(),
+                errors => format!(", unreadable: {}", errors),
+            },
+            match options.stale_since() {
+                Some(since) => format!(
+                    ", not modified since {}: {}",
+                    format_time(since),
+                    measure.format(Byteable(directory_entry.stale_size(since, measure)))
+                ),
+                None => String::new(),
+            },
+            options.time_column.map_or(String::new(), |kind| format!(", dates {}", kind)),
+            if status.is_watching { ", watching for changes" } else { "" },
+            status.snapshot.as_ref().map_or(String::new(), |(root, taken)| format!(
+                ", snapshot of {} taken {}",
+                root.display(),
+                format_time_of_day(*taken)
+            )),
+            match options.pane {
+                Pane::Entries => String::new(),
+                Pane::Owners => ", by user and group".to_string(),
+                Pane::Extensions => ", by extension".to_string(),
+                Pane::ExtensionFiles => format!(", largest {} files", format_extension(&panes.extension)),
+                Pane::Largest => ", largest files and folders anywhere".to_string(),
+                Pane::Treemap => ", as a treemap".to_string(),
+                Pane::Duplicates if status.snapshot.is_some() => String::new(),
+                Pane::Duplicates => match panes.duplicates.as_ref().and_then(|(_, sets)| sets.as_ref()) {
+                    Some(sets) => format!(
+                        ", duplicates wasting: {}",
+                        Byteable(sets.iter().map(DuplicateSet::wasted).sum())
+                    ),
+                    None => ", finding duplicates…".to_string(),
+                },
+            }
+        )));
+    match status.progress {
+        Some(progress) => root_layout.child(ScanProgressView::new(progress)),
+        None => root_layout,
+    }
 }
 
-fn create_more_entry(
-    path: &Path, page_size: u8, page: usize, hide_comments: bool, show_hidden: bool,
-) -> SelectableTextView {
+fn create_more_entry(path: &Path, options: ViewOptions, page: usize) -> SelectableTextView {
     SelectableTextView::new(
         path,
         "⮯ more…".to_string(),
         String::new(),
+        String::new(),
+        String::new(),
         "".to_string(),
         Style::from(Effect::Simple),
         true,
-        page_size,
+        options,
         page + 1,
-        hide_comments,
-        show_hidden,
         Color::Rgb(255, 255, 255),
     )
 }
 
-fn create_back_entry(
-    directory_tree: &DirectoryEntry, page_size: u8, hide_comments: bool, show_hidden: bool,
-) -> Option<SelectableTextView> {
+fn create_back_entry(directory_tree: &DirectoryEntry, options: ViewOptions) -> Option<SelectableTextView> {
     directory_tree.get_parent().map(|path| {
         SelectableTextView::new(
             path,
             "⮬..".to_string(),
             String::new(),
+            String::new(),
+            String::new(),
             "".to_string(),
             Style::from(Effect::Simple),
             true,
-            page_size,
+            options,
             0,
-            hide_comments,
-            show_hidden,
             Color::Rgb(255, 255, 255),
         )
     })
 }
 
-fn create_view_entry(
-    branch: &DirectoryEntry, page_size: u8, hide_comments: bool, show_hidden: bool,
-) -> SelectableTextView {
+/// With a `stale_size` that is shown instead of the entry's size.
+fn create_view_entry(branch: &DirectoryEntry, options: ViewOptions, stale_size: Option<u64>) -> SelectableTextView {
+    let size = stale_size.map(Byteable).or_else(|| branch.size(options.measure));
     SelectableTextView::new(
         branch.path(),
-        branch.name(),
+        branch.listed_name(),
         get_comment_for_entry(branch),
-        branch.len_str(),
+        branch.counts().map_or(String::new(), format_counts),
+        options.time_column.and_then(|kind| branch.times().get(kind)).map_or(String::new(), format_time),
+        match stale_size {
+            Some(stale_size) => options.measure.format(Byteable(stale_size)),
+            None => branch.len_str(options.measure),
+        },
         match branch {
             DirectoryEntry::Folder { .. } => Style::from(Effect::Simple),
             DirectoryEntry::Link { .. } => Style::from(Effect::Simple),
             DirectoryEntry::Excluded { .. } => Style::from(Effect::Simple),
+            DirectoryEntry::MountPoint { .. } => Style::from(Effect::Simple),
+            DirectoryEntry::Unreadable { .. } => Style::from(Effect::Strikethrough),
             DirectoryEntry::File { .. } => Style::from(Effect::Italic),
             DirectoryEntry::Rollup { .. } => Style::from(Effect::Italic),
         },
         match branch {
             DirectoryEntry::File { .. } => false,
             DirectoryEntry::Excluded { .. } => false,
+            DirectoryEntry::MountPoint { .. } => true,
+            DirectoryEntry::Unreadable { .. } => false,
             DirectoryEntry::Link { .. } => false,
-            DirectoryEntry::Folder { .. } => branch.has_children(),
+            DirectoryEntry::Folder { .. } => branch.has_children() || branch.is_collapsed(),
             DirectoryEntry::Rollup { .. } => false, // todo this is just "in the meantime"
         },
-        page_size,
+        options,
         0,
-        hide_comments,
-        show_hidden,
-        branch.len().map_or(Color::Rgb(255, 255, 255), |size| color_for_size(size.0)),
+        size.map_or(Color::Rgb(255, 255, 255), |size| color_for_size(size.0, options.measure)),
     )
 }
 
-fn get_comment_for_entry(branch: &DirectoryEntry) -> String {
-    let path = match branch {
-        DirectoryEntry::File { path, .. } => path.display().to_string(),
-        DirectoryEntry::Link { path, .. } => path.display().to_string(),
-        DirectoryEntry::Folder { path, .. } => path.display().to_string(),
-        DirectoryEntry::Excluded { path, .. } => path.display().to_string(),
-        DirectoryEntry::Rollup { .. } => String::from(""),
-    };
-    let mut comment = String::new();
-    for (a_comment, regex_set) in PATTERNS.iter() {
-        if let Ok(true) = regex_set.as_ref().map(|set| set.is_match(&path)) {
-            comment += a_comment;
-            comment += " ";
-        }
+fn format_time(time: SystemTime) -> String { DateTime::<Local>::from(time).format("%Y-%m-%d").to_string() }
+
+fn format_time_of_day(time: SystemTime) -> String { DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M").to_string() }
+
+/// What share `size` is of `total` as a percentage, or nothing when there is no total to be a share of.
+fn format_share(size: u64, total: u64) -> String {
+    match total {
+        0 => String::new(),
+        total => format!("{:.1}%", size as f64 * 100.0 / total as f64),
     }
-    comment
 }
 
-fn color_for_size(size: u64) -> Color {
-    let (r, g, b) = convert_file_size_to_color(size);
+/// The share `size` is of `total` as a bar in eighths of a block, so that entries of about the same size can be told
+/// apart.
+fn format_bar(size: u64, total: u64, width: usize) -> String {
+    const PARTS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+    let share = if total == 0 { 0.0 } else { size as f64 / total as f64 };
+    let eighths = (share.clamp(0.0, 1.0) * width as f64 * 8.0).round() as usize;
+    format!("{}{}", "█".repeat(eighths / 8), PARTS[eighths % 8])
+}
+
+/// The total is only shown when there are entries that are neither files nor folders, such as links.
+fn format_counts(counts: EntryCounts) -> String {
+    if counts.total == counts.files + counts.dirs {
+        format!("{} files, {} dirs", counts.files, counts.dirs)
+    } else {
+        format!("{} files, {} dirs, {} total", counts.files, counts.dirs, counts.total)
+    }
+}
+
+fn color_for_size(size: u64, measure: SizeMeasure) -> Color {
+    let (r, g, b) = match measure {
+        SizeMeasure::EntryCount => convert_entry_count_to_color(size),
+        _ => convert_file_size_to_color(size),
+    };
     Color::Rgb(r, g, b)
 }
 
@@ -225,6 +815,7 @@ mod tests {
     // use crate::file_analysis::file_types::DirectoryTree;
 
     // use crate::tui::build_views;
+    use crate::tui::{format_bar, format_share};
 
     #[test]
     #[ignore]
@@ -233,4 +824,21 @@ mod tests {
         // let _views = build_views(tree);
         todo!()
     }
+
+    #[test]
+    fn test_format_bar() {
+        assert_eq!("", format_bar(0, 10, 10));
+        assert_eq!("█████", format_bar(5, 10, 10));
+        assert_eq!("██▌", format_bar(25, 100, 10));
+        assert_eq!("▏", format_bar(1, 80, 10));
+        assert_eq!("██████████", format_bar(15, 10, 10));
+        assert_eq!("", format_bar(5, 0, 10));
+    }
+
+    #[test]
+    fn test_format_share() {
+        assert_eq!("25.0%", format_share(1, 4));
+        assert_eq!("33.3%", format_share(1, 3));
+        assert_eq!("", format_share(1, 0));
+    }
 }
@@ -0,0 +1,100 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use cursive::theme::BaseColor::Magenta;
+use cursive::theme::{ColorStyle, Effect, Style};
+use cursive::views::{LinearLayout, TextView};
+use lazy_static::lazy_static;
+
+use crate::file_analysis::file_types::{DirectoryEntry, FileTotal};
+use crate::tui::selectable_text_view::SelectableTextView;
+use crate::tui::{color_for_size, format_share, Pane, ViewOptions};
+
+lazy_static! {
+    static ref USER_NAMES: HashMap<u32, String> = read_names("/etc/passwd");
+    static ref GROUP_NAMES: HashMap<u32, String> = read_names("/etc/group");
+}
+
+fn read_names(path: &str) -> HashMap<u32, String> {
+    fs::read_to_string(path).map(|contents| parse_names(&contents)).unwrap_or_default()
+}
+
+/// Lines of /etc/passwd and /etc/group both start with the name, then the password, then the id.
+fn parse_names(contents: &str) -> HashMap<u32, String> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+/// Lists the users and then the groups that own the files below `directory_entry`, largest first. Selecting one goes
+/// back to the entries.
+pub(crate) fn create_owners_layout(directory_entry: &DirectoryEntry, options: ViewOptions) -> LinearLayout {
+    let mut owners_layout = LinearLayout::vertical();
+    if let Some(owners) = directory_entry.owners() {
+        add_owners(&mut owners_layout, directory_entry, "users", &owners.users, &USER_NAMES, options);
+        add_owners(&mut owners_layout, directory_entry, "groups", &owners.groups, &GROUP_NAMES, options);
+    }
+    if owners_layout.is_empty() {
+        owners_layout.add_child(TextView::new("no owners known"));
+    }
+    owners_layout
+}
+
+fn add_owners(
+    owners_layout: &mut LinearLayout, directory_entry: &DirectoryEntry, heading: &str,
+    totals: &BTreeMap<u32, FileTotal>, names: &HashMap<u32, String>, options: ViewOptions,
+) {
+    if totals.is_empty() {
+        return;
+    }
+    let measure = options.measure;
+    let folder_size = directory_entry.size(measure).map_or(0, |size| size.0);
+    let mut totals: Vec<(&u32, &FileTotal)> = totals.iter().collect();
+    totals.sort_by_key(|&(_, total)| Reverse(total.size(measure).0));
+
+    owners_layout.add_child(TextView::new(heading).style(Style::from(ColorStyle::front(Magenta))));
+    let entries_options = ViewOptions { pane: Pane::Entries, ..options };
+    for (id, total) in totals.into_iter().take(options.page_size as usize) {
+        let size = total.size(measure);
+        owners_layout.add_child(SelectableTextView::new(
+            directory_entry.path(),
+            names.get(id).cloned().unwrap_or_else(|| id.to_string()),
+            format_share(size.0, folder_size),
+            format!("{} files", total.files),
+            String::new(),
+            measure.format(size),
+            Style::from(Effect::Simple),
+            true,
+            entries_options,
+            0,
+            color_for_size(size.0, measure),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tui::owners_view::parse_names;
+
+    #[test]
+    fn test_parse_names() {
+        let names = parse_names(
+            "# a comment\nroot:x:0:0:root:/root:/bin/bash\njames:x:1000:1000:James,,,:/home/james:/bin/bash\nbroken\n",
+        );
+
+        assert_eq!(2, names.len());
+        assert_eq!(Some(&"root".to_string()), names.get(&0));
+        assert_eq!(Some(&"james".to_string()), names.get(&1000));
+
+        let groups = parse_names("sudo:x:27:james\nusers:x:100:");
+        assert_eq!(Some(&"users".to_string()), groups.get(&100));
+    }
+}
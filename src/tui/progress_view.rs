@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use cursive::{Printer, Vec2, View};
+
+use crate::file_analysis::progress::ScanProgress;
+
+/// Redraws from the shared `ScanProgress` on every refresh, so needs cursive's autorefresh to be on while scanning.
+pub(crate) struct ScanProgressView {
+    progress: Arc<ScanProgress>,
+}
+
+impl ScanProgressView {
+    pub(crate) fn new(progress: Arc<ScanProgress>) -> Self { Self { progress } }
+}
+
+impl View for ScanProgressView {
+    fn draw(&self, printer: &Printer) {
+        let report = self.progress.report();
+        printer.print((0, 0), &format!("scanning {}…", report.current_path.display()));
+        printer
+            .print((0, 1), &format!("entries: {}, size: {}, errors: {}", report.entries, report.bytes, report.errors));
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 { Vec2::new(constraint.x, 2) }
+}
@@ -4,15 +4,18 @@ use std::process::Command;
 
 use cursive::align::HAlign;
 use cursive::direction::Direction;
-use cursive::event::{Event, EventResult, Key, MouseButton, MouseEvent};
+use cursive::event::{Callback, Event, EventResult, Key, MouseButton, MouseEvent};
 use cursive::theme::Color::TerminalDefault;
 use cursive::theme::{Color, ColorStyle, ColorType, Effect, Style};
 use cursive::traits::{Finder, Resizable};
-use cursive::view::{CannotFocus, Nameable, Selector};
+use cursive::view::{CannotFocus, Nameable, Selector, ViewWrapper};
 use cursive::views::{DummyView, Layer, LinearLayout, TextView};
 use cursive::{Cursive, Printer, Vec2, View};
 
-use crate::tui::show;
+use crate::tui::{format_bar, format_share, show, ViewOptions};
+
+/// How many characters long a bar of a whole folder is.
+const BAR_WIDTH: usize = 20;
 
 /* todo this is really at least 2 structs, one for actual fs entries and one for meta entries like more and <other files...>
 eg page and page_size might only be necessary for more; comment and size for fs entries */
@@ -21,17 +24,17 @@ pub(crate) struct SelectableTextView {
     selectable: bool,
     color: Color,
     path: PathBuf,
-    page_size: u8,
+    options: ViewOptions,
     page: usize,
-    hide_comments: bool,
-    show_hidden: bool,
+    /// What selecting it does instead of showing the view for its path.
+    on_select: Option<Callback>,
 }
 
 impl SelectableTextView {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        path: &Path, name: String, comment: String, size: String, mut style: Style, selectable: bool, page_size: u8,
-        page: usize, hide_comments: bool, show_hidden: bool, color: Color,
+        path: &Path, name: String, comment: String, counts: String, time: String, size: String, mut style: Style,
+        selectable: bool, options: ViewOptions, page: usize, color: Color,
     ) -> Self {
         let mut name_view = TextView::new(name);
         let mut size_view = TextView::new(size).h_align(HAlign::Right);
@@ -46,17 +49,72 @@ impl SelectableTextView {
         let mut linear_layout =
             LinearLayout::horizontal().child(name_view.with_name("").full_width()).child(DummyView.fixed_width(1));
 
-        if !hide_comments {
+        if !options.hide_comments {
             linear_layout = linear_layout
                 .child(TextView::new(comment).with_name("comment").fixed_width(45))
                 .child(DummyView.fixed_width(1));
         }
 
-        linear_layout = linear_layout.child(size_view.with_name("").fixed_width(10));
+        if options.show_counts {
+            linear_layout = linear_layout
+                .child(TextView::new(counts).h_align(HAlign::Right).style(color).fixed_width(30))
+                .child(DummyView.fixed_width(1));
+        }
+
+        if options.time_column.is_some() {
+            linear_layout = linear_layout
+                .child(TextView::new(time).h_align(HAlign::Right).style(color).fixed_width(10))
+                .child(DummyView.fixed_width(1));
+        }
+
+        linear_layout = linear_layout.child(size_view.with_name("").fixed_width(11));
         let inner_view = Layer::new(linear_layout);
-        Self { inner_view, selectable, color, path: path.to_path_buf(), page_size, page, hide_comments, show_hidden }
+        Self { inner_view, selectable, color, path: path.to_path_buf(), options, page, on_select: None }
     }
 
+    pub(crate) fn on_select(mut self, callback: impl Fn(&mut Cursive) + 'static) -> Self {
+        self.on_select = Some(Callback::from_fn(callback));
+        self
+    }
+
+    /// Adds the columns of the shares its `size` is of its folder and of the whole tree that the options show, before
+    /// its size.
+    pub(crate) fn with_shares(mut self, size: Option<u64>, parent_size: Option<u64>, root_size: Option<u64>) -> Self {
+        let color = self.color;
+        let options = self.options;
+        self.inner_view.with_view_mut(|linear_layout| {
+            let share_of = |total: Option<u64>, format: &dyn Fn(u64, u64) -> String| {
+                size.zip(total).map_or(String::new(), |(size, total)| format(size, total))
+            };
+            let mut index = linear_layout.len() - 1;
+            let mut insert = |linear_layout: &mut LinearLayout, view: TextView, width: usize| {
+                linear_layout.insert_child(index, view.with_name("").fixed_width(width));
+                linear_layout.insert_child(index + 1, DummyView.fixed_width(1));
+                index += 2;
+            };
+            if options.show_percentages {
+                for total in [parent_size, root_size] {
+                    insert(
+                        linear_layout,
+                        TextView::new(share_of(total, &format_share)).h_align(HAlign::Right).style(color),
+                        6,
+                    );
+                }
+            }
+            if options.show_graph {
+                insert(
+                    linear_layout,
+                    TextView::new(share_of(parent_size, &|size, total| format_bar(size, total, BAR_WIDTH)))
+                        .style(color),
+                    BAR_WIDTH,
+                );
+            }
+        });
+        self
+    }
+
+    pub(crate) fn path(&self) -> &Path { &self.path }
+
     pub(crate) fn select_style(&mut self, select: bool) {
         let (front, back) = if select {
             (ColorType::Color(Color::Rgb(0, 0, 0)), ColorType::Color(self.color))
@@ -79,14 +137,15 @@ impl SelectableTextView {
     }
 
     fn get_callback(&self) -> Box<dyn Fn(&mut Cursive)> {
+        if let Some(on_select) = self.on_select.clone() {
+            return Box::new(move |siv: &mut Cursive| on_select(siv));
+        }
         let path = self.path.clone();
-        let page_size = self.page_size;
+        let options = self.options;
         let page = self.page;
-        let hide_comments = self.hide_comments;
-        let show_hidden = self.show_hidden;
         Box::new({
             move |siv: &mut Cursive| {
-                show(page_size, page, hide_comments, show_hidden, &path, siv);
+                show(options, page, &path, siv);
             }
         })
     }
@@ -0,0 +1,300 @@
+use std::path::PathBuf;
+
+use cursive::direction::Direction;
+use cursive::event::{Event, EventResult, Key, MouseButton, MouseEvent};
+use cursive::theme::{Color, ColorStyle};
+use cursive::view::CannotFocus;
+use cursive::views::LinearLayout;
+use cursive::{Printer, Vec2, View};
+
+use crate::file_analysis::file_types::DirectoryEntry;
+use crate::patterns::get_comment_for_entry;
+use crate::tui::{color_for_size, create_back_entry, show, ViewOptions};
+
+/// A rectangle of the treemap, in character cells.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub(crate) struct Rect {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) width: f64,
+    pub(crate) height: f64,
+}
+
+/// An entry drawn in the treemap.
+struct Block {
+    path: PathBuf,
+    name: String,
+    size: String,
+    comment: String,
+    color: Color,
+    /// Whether selecting it shows what is in it.
+    is_openable: bool,
+}
+
+/// The entries of a folder as rectangles with areas proportional to their sizes, as squarely as they can be.
+pub(crate) struct TreemapView {
+    blocks: Vec<Block>,
+    /// Where each block is drawn, from its first cell up to but not including its last, for the last layout.
+    cells: Vec<(Vec2, Vec2)>,
+    sizes: Vec<u64>,
+    selected: usize,
+    options: ViewOptions,
+}
+
+/// The current folder as a treemap, below the entry to go back to its parent.
+pub(crate) fn create_treemap_layout(
+    directory_entry: &DirectoryEntry, options: ViewOptions, is_root: bool,
+) -> LinearLayout {
+    let mut treemap_layout = LinearLayout::vertical();
+    if let Some(back) = create_back_entry(directory_entry, options).filter(|_| !is_root) {
+        treemap_layout.add_child(back);
+    }
+    treemap_layout.child(TreemapView::new(directory_entry, options))
+}
+
+impl TreemapView {
+    fn new(directory_entry: &DirectoryEntry, options: ViewOptions) -> Self {
+        let measure = options.measure;
+        // entries of no size have nowhere to be drawn
+        let entries: Vec<(&DirectoryEntry, u64)> = directory_entry
+            .entries()
+            .into_iter()
+            .flatten()
+            .filter(|entry| options.show_hidden || !entry.is_hidden())
+            .filter_map(|entry| entry.size(measure).map(|size| (entry, size.0)).filter(|(_, size)| *size > 0))
+            .collect();
+        let blocks = entries
+            .iter()
+            .map(|(entry, size)| Block {
+                path: entry.path().to_path_buf(),
+                name: entry.listed_name(),
+                size: entry.len_str(measure),
+                comment: get_comment_for_entry(entry).trim_end().to_string(),
+                color: color_for_size(*size, measure),
+                is_openable: match entry {
+                    DirectoryEntry::Folder { .. } => entry.has_children() || entry.is_collapsed(),
+                    DirectoryEntry::MountPoint { .. } => true,
+                    _ => false,
+                },
+            })
+            .collect();
+        let sizes = entries.iter().map(|(_, size)| *size).collect();
+        Self { blocks, cells: vec![], sizes, selected: 0, options }
+    }
+
+    /// The block drawn at a cell.
+    fn block_at(&self, position: Vec2) -> Option<usize> {
+        self.cells.iter().position(|(start, end)| position.fits(*start) && end.fits(position + (1, 1)))
+    }
+
+    /// The nearest block whose centre is past the selected one's in a direction, weighing how far off to the side it
+    /// is more than how far along.
+    fn next_block(&self, (dx, dy): (isize, isize)) -> Option<usize> {
+        let centre = |(start, end): (Vec2, Vec2)| ((start.x + end.x) as isize, (start.y + end.y) as isize);
+        let (x, y) = centre(*self.cells.get(self.selected)?);
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, (start, end))| start.x < end.x && start.y < end.y)
+            .filter_map(|(index, cells)| {
+                let (other_x, other_y) = centre(*cells);
+                let along = (other_x - x) * dx + (other_y - y) * dy;
+                let aside = (other_x - x) * dy + (other_y - y) * dx;
+                (along > 0).then_some((along + 2 * aside.abs(), index))
+            })
+            .min()
+            .map(|(_, index)| index)
+    }
+
+    fn select(&mut self, index: usize) -> EventResult {
+        self.selected = index;
+        EventResult::Consumed(None)
+    }
+
+    /// Shows what is in the selected block, as the list does.
+    fn open(&self) -> EventResult {
+        match self.blocks.get(self.selected).filter(|block| block.is_openable) {
+            Some(block) => {
+                let (path, options) = (block.path.clone(), self.options);
+                EventResult::with_cb(move |siv| show(options, 0, &path, siv))
+            }
+            None => EventResult::Consumed(None),
+        }
+    }
+}
+
+impl View for TreemapView {
+    fn draw(&self, printer: &Printer) {
+        for (index, (block, (start, end))) in self.blocks.iter().zip(&self.cells).enumerate() {
+            let is_selected = index == self.selected && printer.focused;
+            let fill = if is_selected { "▓" } else { "█" };
+            printer.with_color(ColorStyle::front(block.color), |printer| {
+                for y in start.y..end.y {
+                    for x in start.x..end.x {
+                        // the last column and row are lighter so that blocks of the same colour can be told apart
+                        let is_edge =
+                            (x + 1 == end.x && end.x - start.x > 1) || (y + 1 == end.y && end.y - start.y > 1);
+                        printer.print((x, y), if is_edge && !is_selected { "▒" } else { fill });
+                    }
+                }
+            });
+            let width = (end.x - start.x).saturating_sub(1);
+            if width > 0 && end.y > start.y {
+                let label: String = format!("{} {}", block.name, block.size).chars().take(width).collect();
+                let style = match is_selected {
+                    true => ColorStyle::new(Color::Rgb(255, 255, 255), Color::Rgb(0, 0, 0)),
+                    false => ColorStyle::new(Color::Rgb(0, 0, 0), block.color),
+                };
+                printer.with_color(style, |printer| printer.print(*start, &label));
+            }
+        }
+        // the selected entry in full, as its label may not fit
+        if let Some(block) = self.blocks.get(self.selected) {
+            let status = format!("{} {}  {}", block.name, block.size, block.comment);
+            printer.with_color(ColorStyle::front(block.color), |printer| {
+                printer.print((0, printer.size.y.saturating_sub(1)), status.trim_end())
+            });
+        }
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        // cells are about twice as high as they are wide, so the rectangles are laid out square in half cells
+        let area = Rect { x: 0.0, y: 0.0, width: size.x as f64, height: size.y.saturating_sub(1) as f64 * 2.0 };
+        let to_cell = |value: f64| value.round().max(0.0) as usize;
+        self.cells = squarify(&self.sizes, area)
+            .into_iter()
+            .map(|rect| {
+                let start = Vec2::new(to_cell(rect.x), to_cell(rect.y / 2.0));
+                let end = Vec2::new(to_cell(rect.x + rect.width), to_cell((rect.y + rect.height) / 2.0));
+                (start, end)
+            })
+            .collect();
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 { constraint }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        let direction = match event {
+            Event::Key(Key::Left) => (-1, 0),
+            Event::Key(Key::Right) => (1, 0),
+            Event::Key(Key::Up) => (0, -1),
+            Event::Key(Key::Down) => (0, 1),
+            Event::Key(Key::Enter) => return self.open(),
+            Event::Mouse { offset, position, event: MouseEvent::Press(MouseButton::Left) } => {
+                return match position.checked_sub(offset).and_then(|position| self.block_at(position)) {
+                    Some(index) => self.select(index),
+                    None => EventResult::Ignored,
+                };
+            }
+            Event::Mouse { offset, position, event: MouseEvent::Release(MouseButton::Left) } => {
+                return match position.checked_sub(offset).and_then(|position| self.block_at(position)) {
+                    Some(index) => {
+                        self.selected = index;
+                        self.open()
+                    }
+                    None => EventResult::Ignored,
+                };
+            }
+            _ => return EventResult::Ignored,
+        };
+        // ignored at the edges, so that focus can move on to the entry above
+        self.next_block(direction).map_or(EventResult::Ignored, |index| self.select(index))
+    }
+
+    fn take_focus(&mut self, _source: Direction) -> Result<EventResult, CannotFocus> {
+        if self.blocks.is_empty() {
+            Err(CannotFocus)
+        } else {
+            Ok(EventResult::Consumed(None))
+        }
+    }
+}
+
+/// Lays out `sizes`, largest first, as rectangles filling `area` with areas proportional to them. Each row of
+/// rectangles along the shorter side of what is left takes as many of them as keep them closest to squares.
+pub(crate) fn squarify(sizes: &[u64], area: Rect) -> Vec<Rect> {
+    let total: u64 = sizes.iter().sum();
+    if total == 0 || area.width <= 0.0 || area.height <= 0.0 {
+        return vec![Rect::default(); sizes.len()];
+    }
+    let scale = area.width * area.height / total as f64;
+    let areas: Vec<f64> = sizes.iter().map(|size| *size as f64 * scale).collect();
+    let mut rects = Vec::with_capacity(sizes.len());
+    let mut rest = area;
+    let mut start = 0;
+    while start < areas.len() {
+        let side = rest.width.min(rest.height);
+        let mut end = start + 1;
+        while end < areas.len() && worst_ratio(&areas[start..=end], side) <= worst_ratio(&areas[start..end], side) {
+            end += 1;
+        }
+        let row_area: f64 = areas[start..end].iter().sum();
+        if rest.width >= rest.height {
+            // a column down the left
+            let width = if rest.height > 0.0 { row_area / rest.height } else { 0.0 };
+            let mut y = rest.y;
+            for area in &areas[start..end] {
+                let height = if width > 0.0 { area / width } else { 0.0 };
+                rects.push(Rect { x: rest.x, y, width, height });
+                y += height;
+            }
+            rest = Rect { x: rest.x + width, width: (rest.width - width).max(0.0), ..rest };
+        } else {
+            // a row along the top
+            let height = if rest.width > 0.0 { row_area / rest.width } else { 0.0 };
+            let mut x = rest.x;
+            for area in &areas[start..end] {
+                let width = if height > 0.0 { area / height } else { 0.0 };
+                rects.push(Rect { x, y: rest.y, width, height });
+                x += width;
+            }
+            rest = Rect { y: rest.y + height, height: (rest.height - height).max(0.0), ..rest };
+        }
+        start = end;
+    }
+    rects
+}
+
+/// How far from square the least square of a row of `areas` along a side of length `side` is.
+fn worst_ratio(areas: &[f64], side: f64) -> f64 {
+    let sum: f64 = areas.iter().sum();
+    let max = areas.iter().cloned().fold(f64::MIN, f64::max);
+    let min = areas.iter().cloned().fold(f64::MAX, f64::min);
+    let side = side * side;
+    (side * max / (sum * sum)).max(sum * sum / (side * min))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tui::treemap_view::{squarify, Rect};
+
+    #[test]
+    fn test_squarify() {
+        let sizes = [6, 6, 4, 3, 2, 2, 1];
+        let area = Rect { x: 0.0, y: 0.0, width: 6.0, height: 4.0 };
+
+        let rects = squarify(&sizes, area);
+
+        assert_eq!(sizes.len(), rects.len());
+        for (size, rect) in sizes.iter().zip(&rects) {
+            assert!((rect.width * rect.height - *size as f64).abs() < 1e-9);
+            assert!(rect.x >= 0.0 && rect.y >= 0.0);
+            assert!(rect.x + rect.width <= 6.0 + 1e-9 && rect.y + rect.height <= 4.0 + 1e-9);
+        }
+        // the two largest as a column of squarish rectangles down the left
+        assert_eq!(Rect { x: 0.0, y: 0.0, width: 3.0, height: 2.0 }, rects[0]);
+        assert_eq!(Rect { x: 0.0, y: 2.0, width: 3.0, height: 2.0 }, rects[1]);
+    }
+
+    #[test]
+    fn test_squarify_equal_sizes() {
+        let rects = squarify(&[1, 1, 1, 1], Rect { x: 0.0, y: 0.0, width: 2.0, height: 2.0 });
+
+        assert!(rects.iter().all(|rect| (rect.width - 1.0).abs() < 1e-9 && (rect.height - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_squarify_nothing() {
+        assert_eq!(vec![Rect::default()], squarify(&[0], Rect { x: 0.0, y: 0.0, width: 2.0, height: 2.0 }));
+    }
+}